@@ -0,0 +1,30 @@
+//! The private account identifier of an email sender.
+//!
+//! Email wallets map one sender to one smart account. Committing the sender's address, or its
+//! plain hash, would let anyone look up which account belongs to a known address, so the address
+//! is hashed together with a salt that only the account owner and the relayer know.
+
+use crate::alignment::from_address;
+use crate::dkim::SignedContent;
+use sha2::{Digest, Sha256};
+
+/// The length of the account salt.
+pub const ACCOUNT_SALT_LEN: usize = 32;
+
+/// SHA-256 of the lowercased address followed by the salt.
+pub fn account_hash(address: &str, salt: &[u8; ACCOUNT_SALT_LEN]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(address.to_ascii_lowercase().as_bytes());
+    hasher.update(salt);
+    hasher.finalize().into()
+}
+
+/// The account hash of the address in the bottom-most signed `From` header, if it holds a
+/// single address.
+pub fn signed_account_hash(
+    signed: &SignedContent,
+    salt: &[u8; ACCOUNT_SALT_LEN],
+) -> Option<[u8; 32]> {
+    let from = signed.header("From").and_then(from_address)?;
+    Some(account_hash(&from, salt))
+}
@@ -0,0 +1,202 @@
+//! DMARC-style alignment of the `From` header with the signing domain.
+//!
+//! A valid signature only shows that `d=` sent the message. Unless the domain of the signed `From`
+//! address lines up with `d=`, any sender can be paired with a large provider's signature.
+
+use crate::dkim::SignedContent;
+use mailparse::{addrparse, MailAddr};
+
+/// How closely the `From` domain matches `d=`, from weakest to strongest.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Alignment {
+    /// `From` is unsigned, does not hold exactly one address, or belongs to another organisation.
+    Unaligned = 0,
+    /// One of `From` and `d=` is a subdomain of the other, below a registrable domain. This is
+    /// DMARC `adkim=r` without the sibling subdomains, which can only be told apart from separate
+    /// organisations with the full Public Suffix List.
+    Relaxed = 1,
+    /// `From` and `d=` are the same domain, as in DMARC `adkim=s`.
+    Strict = 2,
+}
+
+impl TryFrom<u8> for Alignment {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Unaligned),
+            1 => Ok(Self::Relaxed),
+            2 => Ok(Self::Strict),
+            other => Err(other),
+        }
+    }
+}
+
+/// Public suffixes of more than one label under which the senders we support register domains.
+///
+/// This stands in for the Public Suffix List, which is too large to carry into the program. Any
+/// other domain whose second-level label is in [`SECOND_LEVEL_LABELS`] may be under a suffix this
+/// list lacks, and has no known organisational domain.
+const MULTI_LABEL_SUFFIXES: [&str; 12] = [
+    "co.in", "net.in", "org.in", "gov.in", "ac.in", "co.uk", "org.uk", "com.au", "com.br", "co.jp",
+    "com.sg", "co.za",
+];
+
+/// Second-level labels registries use for public suffixes such as `co.kr`, `gov.uk` or `com.cn`.
+const SECOND_LEVEL_LABELS: [&str; 14] = [
+    "ac", "co", "com", "edu", "go", "gob", "gov", "ltd", "mil", "ne", "net", "or", "org", "plc",
+];
+
+/// Aligns the bottom-most signed `From` header against the signature's `d=`.
+pub fn from_alignment(signed: &SignedContent) -> Alignment {
+    let Some(from_domain) = signed.header("From").and_then(from_address_domain) else {
+        return Alignment::Unaligned;
+    };
+    align(&from_domain, &signed.signature.domain)
+}
+
+/// Compares an author domain with a signing domain.
+pub fn align(from_domain: &str, signing_domain: &str) -> Alignment {
+    let from_domain = from_domain.trim_end_matches('.').to_ascii_lowercase();
+    let signing_domain = signing_domain.trim_end_matches('.').to_ascii_lowercase();
+    if from_domain == signing_domain {
+        return Alignment::Strict;
+    }
+    let (parent, child) = if from_domain.len() < signing_domain.len() {
+        (&from_domain, &signing_domain)
+    } else {
+        (&signing_domain, &from_domain)
+    };
+    // Whoever controls a registrable domain controls its subdomains too, but not their siblings:
+    // `alice.github.io` and `mallory.github.io` share a parent they do not own.
+    if child.ends_with(&format!(".{}", parent)) && organizational_domain(parent).is_some() {
+        Alignment::Relaxed
+    } else {
+        Alignment::Unaligned
+    }
+}
+
+/// The lowercased address of the single mailbox in a `From` header value.
+pub fn from_address(value: &str) -> Option<String> {
+    let addresses = addrparse(value).ok()?;
+    let [MailAddr::Single(mailbox)] = addresses.as_slice() else {
+        return None;
+    };
+    let (local, domain) = mailbox.addr.rsplit_once('@')?;
+    (!local.is_empty() && !domain.is_empty()).then(|| mailbox.addr.to_ascii_lowercase())
+}
+
+/// The domain of the single mailbox in a `From` header value.
+pub fn from_address_domain(value: &str) -> Option<String> {
+    let address = from_address(value)?;
+    let (_, domain) = address.rsplit_once('@')?;
+    Some(domain.to_string())
+}
+
+/// The registered domain: one label below the public suffix. `domain` must be lowercase, without
+/// a trailing dot.
+///
+/// `None` when `domain` is itself a public suffix, or when its suffix cannot be told without the
+/// Public Suffix List.
+pub fn organizational_domain(domain: &str) -> Option<&str> {
+    let labels = if MULTI_LABEL_SUFFIXES
+        .iter()
+        .any(|suffix| domain.ends_with(&format!(".{}", suffix)))
+    {
+        3
+    } else {
+        let mut labels = domain.rsplit('.');
+        let top_level = labels.next()?;
+        let second_level = labels.next()?;
+        if top_level.len() == 2 && SECOND_LEVEL_LABELS.contains(&second_level) {
+            return None;
+        }
+        2
+    };
+    match domain.rmatch_indices('.').nth(labels - 1) {
+        Some((index, _)) => Some(&domain[index + 1..]),
+        None => (domain.split('.').count() == labels).then_some(domain),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_organizational_domains() {
+        assert_eq!(organizational_domain("paypal.com"), Some("paypal.com"));
+        assert_eq!(organizational_domain("mail.paypal.com"), Some("paypal.com"));
+        assert_eq!(organizational_domain("alerts.sbi.co.in"), Some("sbi.co.in"));
+        assert_eq!(organizational_domain("sbi.co.in"), Some("sbi.co.in"));
+        assert_eq!(
+            organizational_domain("a.b.example.co.uk"),
+            Some("example.co.uk")
+        );
+    }
+
+    #[test]
+    fn suffixes_have_no_organizational_domain() {
+        assert_eq!(organizational_domain("com"), None);
+        assert_eq!(organizational_domain("co.in"), None);
+        assert_eq!(organizational_domain("co.uk"), None);
+    }
+
+    #[test]
+    fn unknown_multi_label_suffixes_have_no_organizational_domain() {
+        assert_eq!(organizational_domain("victim.co.kr"), None);
+        assert_eq!(organizational_domain("hmrc.gov.uk"), None);
+        assert_eq!(organizational_domain("bank.com.cn"), None);
+        assert_eq!(organizational_domain("co.kr"), None);
+    }
+
+    #[test]
+    fn aligns_same_domain_strictly() {
+        assert_eq!(align("PayPal.com.", "paypal.com"), Alignment::Strict);
+        assert_eq!(
+            align("alerts.sbi.co.in", "alerts.sbi.co.in"),
+            Alignment::Strict
+        );
+    }
+
+    #[test]
+    fn aligns_subdomains_relaxed() {
+        assert_eq!(align("mail.paypal.com", "paypal.com"), Alignment::Relaxed);
+        assert_eq!(align("sbi.co.in", "alerts.sbi.co.in"), Alignment::Relaxed);
+        assert_eq!(align("alice.github.io", "github.io"), Alignment::Relaxed);
+    }
+
+    #[test]
+    fn refuses_separate_organisations() {
+        assert_eq!(
+            align("victim.co.kr", "attacker.co.kr"),
+            Alignment::Unaligned
+        );
+        assert_eq!(align("hmrc.gov.uk", "evil.gov.uk"), Alignment::Unaligned);
+        assert_eq!(align("bank.com.cn", "evil.com.cn"), Alignment::Unaligned);
+        assert_eq!(
+            align("alice.github.io", "mallory.github.io"),
+            Alignment::Unaligned
+        );
+        assert_eq!(align("victim.co.kr", "co.kr"), Alignment::Unaligned);
+        assert_eq!(align("paypal.com", "com"), Alignment::Unaligned);
+        assert_eq!(align("paypal.com", "evilpaypal.com"), Alignment::Unaligned);
+        assert_eq!(align("sbi.co.in", "hdfc.co.in"), Alignment::Unaligned);
+    }
+
+    #[test]
+    fn reads_the_single_from_address() {
+        assert_eq!(
+            from_address("PayPal <Service@PayPal.com>").as_deref(),
+            Some("service@paypal.com")
+        );
+        assert_eq!(
+            from_address_domain("a@b.example").as_deref(),
+            Some("b.example")
+        );
+        assert_eq!(from_address("a@example.com, b@example.com"), None);
+        assert_eq!(from_address("Group: a@example.com;"), None);
+        assert_eq!(from_address("not an address"), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Parsing of money amounts into integer minor units.
+//!
+//! Receipts write amounts as "₹ 1,25,000.00", "Rs. 500", "$12.50" or "0.1 PYUSD". Contracts need a
+//! number they can compare, so the amount is committed in minor units (paise, cents) together
+//! with an ISO 4217 currency code.
+
+use core::fmt;
+
+/// Currency markers and the ISO code they stand for, longest first so that "US$" wins over "$"
+/// and "PYUSD" over "USD".
+const CURRENCY_MARKERS: [(&str, [u8; 3]); 8] = [
+    ("PYUSD", *b"USD"),
+    ("US$", *b"USD"),
+    ("USD", *b"USD"),
+    ("INR", *b"INR"),
+    ("Rs.", *b"INR"),
+    ("Rs", *b"INR"),
+    ("₹", *b"INR"),
+    ("$", *b"USD"),
+];
+
+/// The number of minor-unit digits of every supported currency.
+pub const MINOR_UNIT_DIGITS: u32 = 2;
+
+/// An amount of money in minor units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Amount {
+    pub minor_units: u128,
+    /// The ISO 4217 currency code.
+    pub currency: [u8; 3],
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = 10u128.pow(MINOR_UNIT_DIGITS);
+        write!(
+            f,
+            "{}.{:0width$} {}",
+            self.minor_units / scale,
+            self.minor_units % scale,
+            String::from_utf8_lossy(&self.currency),
+            width = MINOR_UNIT_DIGITS as usize
+        )
+    }
+}
+
+/// Parses an amount with a currency symbol or code before or after the number.
+///
+/// Thousands separators may be grouped either way ("1,250,000" or "12,50,000"). More decimal
+/// places than the currency has minor units are rejected rather than rounded.
+pub fn parse_amount(text: &str) -> Option<Amount> {
+    let text = text.trim();
+    let (number, currency) = CURRENCY_MARKERS.iter().find_map(|&(marker, currency)| {
+        strip_prefix_ignore_case(text, marker)
+            .or_else(|| strip_suffix_ignore_case(text, marker))
+            .map(|number| (number.trim(), currency))
+    })?;
+    Some(Amount {
+        minor_units: parse_minor_units(number)?,
+        currency,
+    })
+}
+
+/// Parses a plain decimal number, such as "1,25,000.5", into minor units.
+pub fn parse_minor_units(number: &str) -> Option<u128> {
+    let (integer, fraction) = match number.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (number, ""),
+    };
+    if fraction.len() > MINOR_UNIT_DIGITS as usize || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    // Separators must sit between digits.
+    if integer.is_empty()
+        || integer.split(',').any(|group| group.is_empty())
+        || !integer.bytes().all(|b| b.is_ascii_digit() || b == b',')
+    {
+        return None;
+    }
+
+    let mut minor_units: u128 = 0;
+    for digit in integer.bytes().filter(u8::is_ascii_digit) {
+        minor_units = minor_units
+            .checked_mul(10)?
+            .checked_add(u128::from(digit - b'0'))?;
+    }
+    let mut fraction_units: u128 = 0;
+    for position in 0..MINOR_UNIT_DIGITS as usize {
+        let digit = fraction.as_bytes().get(position).map_or(0, |b| b - b'0');
+        fraction_units = fraction_units * 10 + u128::from(digit);
+    }
+    minor_units
+        .checked_mul(10u128.pow(MINOR_UNIT_DIGITS))?
+        .checked_add(fraction_units)
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+fn strip_suffix_ignore_case<'a>(text: &'a str, suffix: &str) -> Option<&'a str> {
+    let start = text.len().checked_sub(suffix.len())?;
+    let tail = text.get(start..)?;
+    tail.eq_ignore_ascii_case(suffix).then(|| &text[..start])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(minor_units: u128, currency: &[u8; 3]) -> Option<Amount> {
+        Some(Amount {
+            minor_units,
+            currency: *currency,
+        })
+    }
+
+    #[test]
+    fn parses_receipt_amounts() {
+        assert_eq!(parse_amount("₹ 1,25,000.00"), amount(12_500_000, b"INR"));
+        assert_eq!(parse_amount("Rs. 500"), amount(50_000, b"INR"));
+        assert_eq!(parse_amount("Rs.750.00"), amount(75_000, b"INR"));
+        assert_eq!(parse_amount("INR 1,499.00"), amount(149_900, b"INR"));
+        assert_eq!(parse_amount("$12.5"), amount(1_250, b"USD"));
+        assert_eq!(parse_amount("US$1,250,000"), amount(125_000_000, b"USD"));
+        assert_eq!(parse_amount("0.1 PYUSD"), amount(10, b"USD"));
+        assert_eq!(parse_amount("25 usd"), amount(2_500, b"USD"));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(parse_amount("300.00"), None);
+        assert_eq!(parse_amount("$"), None);
+        assert_eq!(parse_amount("$12.505"), None);
+        assert_eq!(parse_amount("$1,,000"), None);
+        assert_eq!(parse_amount("$,100"), None);
+        assert_eq!(parse_amount("$.50"), None);
+        assert_eq!(parse_amount("$1e3"), None);
+        assert_eq!(parse_amount("$-5"), None);
+        assert_eq!(
+            parse_amount("$999999999999999999999999999999999999999"),
+            None
+        );
+    }
+
+    #[test]
+    fn displays_minor_units() {
+        assert_eq!(amount(150_005, b"INR").unwrap().to_string(), "1500.05 INR");
+    }
+}
@@ -0,0 +1,355 @@
+//! The PayCrypt command grammar, read from the signed `Subject` header.
+//!
+//! A user authorises a transfer by sending an email whose subject is a single command:
+//!
+//! ```text
+//! Send 0.1 PYUSD to recipient@example.com
+//! Withdraw 25 USDC to 0x000000000000000000000000000000000000dEaD
+//! Approve 100 PYUSD to vitalik.eth
+//! ```
+//!
+//! Keywords are case-insensitive and words are separated by whitespace. The amount is a plain
+//! decimal number, the token a symbol of up to eleven letters or digits, and the recipient an
+//! email address, an ENS name or a `0x` address.
+//!
+//! A command only counts when it was sent to the relayer, so the relayer's address has to be in
+//! the signed `To` or `Cc` header.
+
+use crate::dkim::SignedContent;
+use core::fmt;
+use mailparse::{addrparse, parse_header, MailAddr};
+
+/// The address users send command emails to.
+pub const DEFAULT_RELAYER: &str = "send@paycrypt.xyz";
+
+/// The most fraction digits an amount may have, matching the usual 18 token decimals.
+pub const MAX_DECIMALS: usize = 18;
+
+/// What the command asks the contract to do.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommandKind {
+    /// No command was found; the other committed command fields are empty.
+    #[default]
+    None = 0,
+    Send = 1,
+    Withdraw = 2,
+    Approve = 3,
+}
+
+impl TryFrom<u8> for CommandKind {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Send),
+            2 => Ok(Self::Withdraw),
+            3 => Ok(Self::Approve),
+            other => Err(other),
+        }
+    }
+}
+
+/// How the recipient of a command is named, committed as `recipient_kind`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecipientKind {
+    #[default]
+    None = 0,
+    Email = 1,
+    Ens = 2,
+    Address = 3,
+}
+
+impl TryFrom<u8> for RecipientKind {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Email),
+            2 => Ok(Self::Ens),
+            3 => Ok(Self::Address),
+            other => Err(other),
+        }
+    }
+}
+
+/// The recipient of a command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recipient {
+    /// A lowercased email address.
+    Email(String),
+    /// A lowercased ENS name ending in `.eth`.
+    Ens(String),
+    /// An Ethereum address.
+    Address([u8; 20]),
+}
+
+impl Recipient {
+    pub fn kind(&self) -> RecipientKind {
+        match self {
+            Self::Email(_) => RecipientKind::Email,
+            Self::Ens(_) => RecipientKind::Ens,
+            Self::Address(_) => RecipientKind::Address,
+        }
+    }
+
+    /// The recipient as committed in `recipient`; addresses are lowercase `0x` hex.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Email(email) => email.clone(),
+            Self::Ens(name) => name.clone(),
+            Self::Address(address) => to_hex(address),
+        }
+    }
+}
+
+/// A parsed command. The amount is `mantissa / 10^decimals` tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Command {
+    pub kind: CommandKind,
+    pub mantissa: u128,
+    pub decimals: u8,
+    /// The uppercased token symbol.
+    pub token: String,
+    pub recipient: Recipient,
+}
+
+impl Command {
+    /// The amount as a decimal number, such as "0.1".
+    pub fn amount_text(&self) -> String {
+        format_decimal(self.mantissa, self.decimals)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {} {} to {}",
+            self.kind,
+            self.amount_text(),
+            self.token,
+            self.recipient.text()
+        )
+    }
+}
+
+/// Writes `mantissa / 10^decimals` as a decimal number.
+pub fn format_decimal(mantissa: impl fmt::Display, decimals: u8) -> String {
+    let digits = mantissa.to_string();
+    let decimals = usize::from(decimals);
+    if decimals == 0 {
+        return digits;
+    }
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    format!("{}.{}", integer, fraction)
+}
+
+/// Parses the command in the bottom-most signed `Subject` header.
+pub fn signed_command(signed: &SignedContent) -> Option<Command> {
+    parse_command(&signed_subject(signed)?)
+}
+
+/// The bottom-most signed `Subject` header, after decoding any encoded words.
+pub(crate) fn signed_subject(signed: &SignedContent) -> Option<String> {
+    let subject = signed.header("Subject")?;
+    let line = format!("Subject: {}\r\n", subject);
+    let (header, _) = parse_header(line.as_bytes()).ok()?;
+    Some(header.get_value())
+}
+
+/// The lowercased addresses in the bottom-most signed `To` and `Cc` headers, including those in
+/// groups.
+pub fn signed_recipients(signed: &SignedContent) -> Vec<String> {
+    let mut recipients = Vec::new();
+    for value in ["To", "Cc"].iter().filter_map(|name| signed.header(name)) {
+        let Ok(addresses) = addrparse(value) else {
+            continue;
+        };
+        for address in addresses.iter() {
+            match address {
+                MailAddr::Single(mailbox) => recipients.push(mailbox.addr.to_ascii_lowercase()),
+                MailAddr::Group(group) => recipients.extend(
+                    group
+                        .addrs
+                        .iter()
+                        .map(|mailbox| mailbox.addr.to_ascii_lowercase()),
+                ),
+            }
+        }
+    }
+    recipients
+}
+
+/// Whether the email was sent to the relayer, going by its signed `To` and `Cc` headers only.
+pub fn addressed_to(signed: &SignedContent, relayer: &str) -> bool {
+    signed_recipients(signed)
+        .iter()
+        .any(|recipient| recipient.eq_ignore_ascii_case(relayer.trim()))
+}
+
+/// Parses a command, which must make up the whole text.
+pub fn parse_command(text: &str) -> Option<Command> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let [verb, amount, token, to, recipient] = words.as_slice() else {
+        return None;
+    };
+
+    let kind = match verb.to_ascii_lowercase().as_str() {
+        "send" => CommandKind::Send,
+        "withdraw" => CommandKind::Withdraw,
+        "approve" => CommandKind::Approve,
+        _ => return None,
+    };
+    if !to.eq_ignore_ascii_case("to") {
+        return None;
+    }
+    let (mantissa, decimals) = parse_decimal(amount)?;
+
+    Some(Command {
+        kind,
+        mantissa,
+        decimals,
+        token: parse_token(token)?,
+        recipient: parse_recipient(recipient)?,
+    })
+}
+
+/// Parses a plain decimal number into its digits and the number of them after the point.
+fn parse_decimal(text: &str) -> Option<(u128, u8)> {
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if integer.is_empty()
+        || fraction.len() > MAX_DECIMALS
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+        || (text.contains('.') && fraction.is_empty())
+    {
+        return None;
+    }
+    let mut mantissa: u128 = 0;
+    for digit in integer.bytes().chain(fraction.bytes()) {
+        mantissa = mantissa
+            .checked_mul(10)?
+            .checked_add(u128::from(digit - b'0'))?;
+    }
+    Some((mantissa, fraction.len() as u8))
+}
+
+fn parse_token(text: &str) -> Option<String> {
+    let valid = (1..=11).contains(&text.len()) && text.bytes().all(|b| b.is_ascii_alphanumeric());
+    valid.then(|| text.to_ascii_uppercase())
+}
+
+fn parse_recipient(text: &str) -> Option<Recipient> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return parse_hex(hex).map(Recipient::Address);
+    }
+
+    let lower = text.to_ascii_lowercase();
+    let is_domain = |domain: &str| {
+        domain.split('.').count() >= 2
+            && domain.split('.').all(|label| {
+                !label.is_empty()
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            })
+    };
+
+    if let Some((local, domain)) = lower.split_once('@') {
+        let valid_local = !local.is_empty()
+            && local
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"._+-".contains(&b));
+        return (valid_local && is_domain(domain)).then(|| Recipient::Email(lower.clone()));
+    }
+    if lower.ends_with(".eth") && is_domain(&lower) {
+        return Some(Recipient::Ens(lower));
+    }
+    None
+}
+
+/// Parses exactly `N` bytes of hex, without a `0x` prefix.
+pub(crate) fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(core::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Writes bytes as lowercase `0x` hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        let command = parse_command("Send 0.1 PYUSD to Recipient@Example.com").unwrap();
+        assert_eq!(command.kind, CommandKind::Send);
+        assert_eq!((command.mantissa, command.decimals), (1, 1));
+        assert_eq!(command.amount_text(), "0.1");
+        assert_eq!(command.token, "PYUSD");
+        assert_eq!(
+            command.recipient,
+            Recipient::Email("recipient@example.com".to_string())
+        );
+
+        let command =
+            parse_command("withdraw 25 usdc TO 0x000000000000000000000000000000000000dEaD")
+                .unwrap();
+        assert_eq!(command.kind, CommandKind::Withdraw);
+        assert_eq!((command.mantissa, command.decimals), (25, 0));
+        assert_eq!(command.token, "USDC");
+        assert_eq!(command.recipient.kind(), RecipientKind::Address);
+        assert_eq!(
+            command.recipient.text(),
+            "0x000000000000000000000000000000000000dead"
+        );
+
+        let command = parse_command("  Approve\t100.50 PYUSD to Vitalik.eth ").unwrap();
+        assert_eq!(command.kind, CommandKind::Approve);
+        assert_eq!(command.amount_text(), "100.50");
+        assert_eq!(command.recipient, Recipient::Ens("vitalik.eth".to_string()));
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        for text in [
+            "",
+            "Send 0.1 PYUSD recipient@example.com",
+            "Send 0.1 PYUSD to recipient@example.com now",
+            "Pay 0.1 PYUSD to recipient@example.com",
+            "Send 0.1 PYUSD from recipient@example.com",
+            "Send .1 PYUSD to recipient@example.com",
+            "Send 1. PYUSD to recipient@example.com",
+            "Send 1,000 PYUSD to recipient@example.com",
+            "Send 0.0000000000000000001 PYUSD to recipient@example.com",
+            "Send 1 PY-USD to recipient@example.com",
+            "Send 1 ABCDEFGHIJKL to recipient@example.com",
+            "Send 1 PYUSD to 0xdead",
+            "Send 1 PYUSD to recipient@localhost",
+            "Send 1 PYUSD to @example.com",
+            "Send 1 PYUSD to vitalik",
+            "Send 1 PYUSD to -vitalik.eth",
+        ] {
+            assert_eq!(parse_command(text), None, "{:?}", text);
+        }
+    }
+}
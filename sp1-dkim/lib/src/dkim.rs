@@ -0,0 +1,742 @@
+//! Locating the content covered by a DKIM signature.
+//!
+//! `cfdkim` only reports whether a signature verifies. Anything read out of the email afterwards
+//! must come from the bytes that signature covers, namely the headers named in `h=` and the
+//! canonicalised body up to `l=`, because everything else can be added after signing.
+
+use crate::policy::check_signed_headers;
+use crate::{ErrorCode, TimeWindow, Timestamps};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::{alphabet, Engine};
+use core::ops::Range;
+use sha2::{Digest, Sha256};
+
+/// A header field as it appears in the message, with folding removed from the value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+/// How a signature canonicalises the header or the body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Canonicalization {
+    Simple,
+    Relaxed,
+}
+
+/// The tags of a `DKIM-Signature` header that decide what it covers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DkimSignature {
+    /// The signing algorithm, `a=`, such as `rsa-sha256`.
+    pub algorithm: String,
+    /// The signing domain, `d=`.
+    pub domain: String,
+    /// The header and body canonicalisation, `c=`.
+    pub canonicalization: (Canonicalization, Canonicalization),
+    /// The signed header names, `h=`, in signing order.
+    pub signed_headers: Vec<String>,
+    /// The number of body bytes covered, `l=`, or `None` for the whole body.
+    pub body_length: Option<usize>,
+    /// The signature itself, `b=`, base64-decoded.
+    pub signature: Vec<u8>,
+    /// When the signature was created, `t=`, in unix seconds.
+    pub timestamp: Option<u64>,
+    /// When the signature expires, `x=`, in unix seconds.
+    pub expiration: Option<u64>,
+}
+
+impl DkimSignature {
+    /// Parses the tag-list of a `DKIM-Signature` header value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let tags = parse_tags(value);
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+
+        let canonicalization = match tag("c") {
+            None => (Canonicalization::Simple, Canonicalization::Simple),
+            Some(c) => {
+                let mut parts = c.splitn(2, '/');
+                let header = parse_canonicalization(parts.next()?)?;
+                let body = match parts.next() {
+                    Some(body) => parse_canonicalization(body)?,
+                    None => Canonicalization::Simple,
+                };
+                (header, body)
+            }
+        };
+        let body_length = match tag("l") {
+            Some(l) => Some(l.parse().ok()?),
+            None => None,
+        };
+        let timestamp = match tag("t") {
+            Some(t) => Some(t.parse().ok()?),
+            None => None,
+        };
+        let expiration = match tag("x") {
+            Some(x) => Some(x.parse().ok()?),
+            None => None,
+        };
+        let signature = SIGNATURE_BASE64.decode(tag("b")?.replace(' ', "")).ok()?;
+
+        Some(Self {
+            algorithm: tag("a")?.to_ascii_lowercase(),
+            domain: tag("d")?.to_string(),
+            canonicalization,
+            signed_headers: tag("h")?
+                .split(':')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            body_length,
+            signature,
+            timestamp,
+            expiration,
+        })
+    }
+
+    /// The key type the algorithm needs, such as `rsa` or `ed25519`.
+    pub fn key_type(&self) -> &str {
+        self.algorithm
+            .split_once('-')
+            .map_or(self.algorithm.as_str(), |(key_type, _)| key_type)
+    }
+
+    /// A value that identifies the signed email, committed so the same receipt cannot be redeemed
+    /// twice.
+    ///
+    /// This is the SHA-256 of the decoded `b=` bytes, so refolding the header or re-encoding the
+    /// base64 does not change it. It only identifies the email when taken from a signature that
+    /// verified: anyone can add another `DKIM-Signature` with a fresh `b=`.
+    pub fn nullifier(&self) -> [u8; 32] {
+        Sha256::digest(&self.signature).into()
+    }
+}
+
+/// Decodes `b=` the way a verifier does: padding is optional and unused trailing bits are
+/// ignored, so every spelling of a signature decodes to the same bytes.
+const SIGNATURE_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_allow_trailing_bits(true)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+fn parse_canonicalization(value: &str) -> Option<Canonicalization> {
+    match value {
+        "simple" => Some(Canonicalization::Simple),
+        "relaxed" => Some(Canonicalization::Relaxed),
+        _ => None,
+    }
+}
+
+/// Splits a DKIM tag-list into `(tag, value)` pairs, with all whitespace removed from the value
+/// except single spaces inside it.
+pub fn parse_tags(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|tag| {
+            let (name, value) = tag.split_once('=')?;
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            Some((name.trim().to_string(), value))
+        })
+        .collect()
+}
+
+/// The headers and body covered by a DKIM signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedContent {
+    pub signature: DkimSignature,
+    /// The instances of the headers listed in `h=`, in signing order.
+    pub headers: Vec<Header>,
+    /// The canonicalised body, truncated to `l=`.
+    pub body: Vec<u8>,
+}
+
+impl SignedContent {
+    /// Collects what the first `DKIM-Signature` with `d=` equal to `domain` covers.
+    ///
+    /// Nothing here says that signature verifies; see [`signature_candidates`].
+    pub fn from_email(raw_email: &[u8], domain: &str) -> Option<Self> {
+        let (headers, body) = split_message(raw_email);
+        let signature = signatures(&headers)
+            .into_iter()
+            .find(|s| s.domain.eq_ignore_ascii_case(domain))?;
+
+        let headers = select_signed_headers(&headers, &signature.signed_headers);
+
+        let mut body = canonicalize_body(body, signature.canonicalization.1);
+        if let Some(length) = signature.body_length {
+            body.truncate(length);
+        }
+
+        Some(Self {
+            signature,
+            headers,
+            body,
+        })
+    }
+
+    /// The value of the bottom-most signed instance of a header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+}
+
+/// Each `DKIM-Signature` with `d=` equal to `domain`, top to bottom, with what it covers and the
+/// email as it reads with every other signature from the domain removed.
+///
+/// `cfdkim` passes an email if any signature from the domain verifies, and anyone can add a
+/// forged one next to the genuine one. Verifying the isolated email checks that candidate alone,
+/// so content is only read from a candidate whose isolated email verifies.
+pub fn signature_candidates(raw_email: &[u8], domain: &str) -> Vec<(SignedContent, Vec<u8>)> {
+    let (fields, header_end) = header_fields(raw_email);
+    let from_domain: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, header))| {
+            header.as_ref().is_some_and(|h| {
+                h.name.eq_ignore_ascii_case("DKIM-Signature") && signs_for(&h.value, domain)
+            })
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    from_domain
+        .iter()
+        .filter_map(|&keep| {
+            let mut isolated = Vec::with_capacity(raw_email.len());
+            for (i, (range, _)) in fields.iter().enumerate() {
+                if i == keep || !from_domain.contains(&i) {
+                    isolated.extend_from_slice(&raw_email[range.clone()]);
+                }
+            }
+            isolated.extend_from_slice(&raw_email[header_end..]);
+            let signed = SignedContent::from_email(&isolated, domain)?;
+            Some((signed, isolated))
+        })
+        .collect()
+}
+
+/// Why [`verify_signed_content`] refused an email.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError<E> {
+    /// No signature from the domain verified. Holds the error of the first one tried, or `None`
+    /// if the domain has no signature that parses.
+    Unverified(Option<E>),
+    /// A signature verified, but its `h=` breaks the header policy.
+    Headers(ErrorCode),
+    /// The verified signature's `x=` is not after its `t=`.
+    ExpiredAtSigning(Timestamps),
+    /// The email was not sent within the window.
+    OutsideWindow(Timestamps),
+}
+
+impl<E> VerifyError<E> {
+    /// The times of the signature that verified, if one did.
+    pub fn timestamps(&self) -> Option<Timestamps> {
+        match self {
+            Self::ExpiredAtSigning(timestamps) | Self::OutsideWindow(timestamps) => {
+                Some(*timestamps)
+            }
+            Self::Unverified(_) | Self::Headers(_) => None,
+        }
+    }
+}
+
+impl VerifyError<ErrorCode> {
+    /// The code the program commits for the error.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Unverified(code) => code.unwrap_or(ErrorCode::ParseError),
+            Self::Headers(code) => *code,
+            Self::ExpiredAtSigning(_) => ErrorCode::SignatureExpired,
+            Self::OutsideWindow(_) => ErrorCode::OutsideWindow,
+        }
+    }
+}
+
+/// Runs the checks every email goes through and returns what its verified signature covers, with
+/// the times it records.
+///
+/// `verify` checks one candidate of [`signature_candidates`] against its isolated email. The
+/// program and the host each pass their own `cfdkim` call, and share everything else here, so the
+/// host's pre-flight check cannot drift from what the program accepts. Content is read from the
+/// first candidate that verifies. That signature must cover the critical headers, with no unsigned
+/// copies beside them, must not have expired before it was made, and must date the email within
+/// `window`.
+pub fn verify_signed_content<E>(
+    raw_email: &[u8],
+    domain: &str,
+    window: TimeWindow,
+    mut verify: impl FnMut(&SignedContent, &[u8]) -> Result<(), E>,
+) -> Result<(SignedContent, Timestamps), VerifyError<E>> {
+    let mut first_error = None;
+    let mut verified = None;
+    for (signed, isolated) in signature_candidates(raw_email, domain) {
+        match verify(&signed, &isolated) {
+            Ok(()) => {
+                verified = Some(signed);
+                break;
+            }
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    let signed = verified.ok_or(VerifyError::Unverified(first_error))?;
+
+    let (headers, _) = split_message(raw_email);
+    check_signed_headers(&headers, &signed.signature).map_err(VerifyError::Headers)?;
+
+    let timestamps = Timestamps::from_signed(&signed);
+    if timestamps.expired_at_signing() {
+        return Err(VerifyError::ExpiredAtSigning(timestamps));
+    }
+    if !window.contains(timestamps.sent_at()) {
+        return Err(VerifyError::OutsideWindow(timestamps));
+    }
+    Ok((signed, timestamps))
+}
+
+/// Whether a `DKIM-Signature` value has `d=` equal to `domain`.
+///
+/// Only the `d=` tag is read, so a signature [`DkimSignature::parse`] rejects but a verifier might
+/// still accept is removed from the other candidates' isolated emails all the same.
+fn signs_for(value: &str, domain: &str) -> bool {
+    parse_tags(value)
+        .iter()
+        .any(|(name, value)| name == "d" && value.eq_ignore_ascii_case(domain))
+}
+
+/// Parses every well-formed `DKIM-Signature` header, top to bottom.
+pub fn signatures(headers: &[Header]) -> Vec<DkimSignature> {
+    headers
+        .iter()
+        .filter(|h| h.name.eq_ignore_ascii_case("DKIM-Signature"))
+        .filter_map(|h| DkimSignature::parse(&h.value))
+        .collect()
+}
+
+/// Splits a message into its unfolded headers and its raw body.
+pub fn split_message(raw_email: &[u8]) -> (Vec<Header>, &[u8]) {
+    let (fields, header_end) = header_fields(raw_email);
+    let headers = fields
+        .into_iter()
+        .filter_map(|(_, header)| header)
+        .collect();
+
+    // Skip the blank line that ends the header.
+    let rest = &raw_email[header_end..];
+    let blank = rest
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(rest.len(), |i| i + 1);
+    (headers, &rest[blank..])
+}
+
+/// A header line's raw byte range, folded lines included, and the header it holds, if any.
+type HeaderField = (Range<usize>, Option<Header>);
+
+/// The raw byte ranges of the header fields, folded lines included, each with its unfolded
+/// header if it is one, and the offset of the blank line that ends the header.
+///
+/// The ranges cover every byte before that offset, including lines that are not header fields.
+fn header_fields(raw_email: &[u8]) -> (Vec<HeaderField>, usize) {
+    let mut fields: Vec<HeaderField> = Vec::new();
+    let mut offset = 0;
+
+    while offset < raw_email.len() {
+        let start = offset;
+        let end = raw_email[offset..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(raw_email.len(), |i| offset + i + 1);
+        let line = &raw_email[offset..end];
+        offset = end;
+
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            return (fields, start);
+        }
+
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with([' ', '\t']) {
+            if let Some((range, header)) = fields.last_mut() {
+                range.end = end;
+                if let Some(header) = header {
+                    header.value.push_str(&line);
+                }
+                continue;
+            }
+        }
+        let header = line.split_once(':').map(|(name, value)| Header {
+            name: name.trim_end().to_string(),
+            value: value.trim_start().to_string(),
+        });
+        fields.push((start..end, header));
+    }
+
+    (fields, raw_email.len())
+}
+
+/// Picks the header instances a signature covers.
+///
+/// Each name in `h=` consumes the bottom-most instance not used yet. Names listed more times than
+/// the header occurs cover nothing, which is how over-signing guards against added headers.
+pub fn select_signed_headers(headers: &[Header], signed_headers: &[String]) -> Vec<Header> {
+    let mut used = vec![false; headers.len()];
+    let mut selected = Vec::new();
+    for name in signed_headers {
+        let instance = (0..headers.len())
+            .rev()
+            .find(|&i| !used[i] && headers[i].name.eq_ignore_ascii_case(name));
+        if let Some(i) = instance {
+            used[i] = true;
+            selected.push(headers[i].clone());
+        }
+    }
+    selected
+}
+
+/// Canonicalises a body as described in RFC 6376, section 3.4.
+pub fn canonicalize_body(body: &[u8], canonicalization: Canonicalization) -> Vec<u8> {
+    let mut lines: Vec<Vec<u8>> = body
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line).to_vec())
+        .collect();
+    // A body ending in CRLF leaves an empty piece after the last line.
+    if body.ends_with(b"\n") {
+        lines.pop();
+    }
+
+    if canonicalization == Canonicalization::Relaxed {
+        for line in lines.iter_mut() {
+            let mut relaxed = Vec::with_capacity(line.len());
+            let mut whitespace = false;
+            for &b in line.iter() {
+                if b == b' ' || b == b'\t' {
+                    whitespace = true;
+                    continue;
+                }
+                if whitespace {
+                    relaxed.push(b' ');
+                }
+                whitespace = false;
+                relaxed.push(b);
+            }
+            *line = relaxed;
+        }
+    }
+
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+
+    if lines.is_empty() {
+        return match canonicalization {
+            Canonicalization::Simple => b"\r\n".to_vec(),
+            Canonicalization::Relaxed => Vec::new(),
+        };
+    }
+
+    let mut canonical = Vec::with_capacity(body.len());
+    for line in lines {
+        canonical.extend_from_slice(&line);
+        canonical.extend_from_slice(b"\r\n");
+    }
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> Header {
+        Header {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn signature(domain: &str, b: &str) -> String {
+        format!(
+            "DKIM-Signature: v=1; a=rsa-sha256; d={}; s=s; c=relaxed/relaxed;\r\n \
+             h=from:to:subject; bh=AAAA; b={}\r\n",
+            domain, b
+        )
+    }
+
+    const MESSAGE: &str =
+        "From: a@example.com\r\nTo: b@example.org\r\nSubject: Paid\r\n\r\nBody\r\n";
+
+    #[test]
+    fn parses_signature_tags() {
+        let signature = DkimSignature::parse(
+            "v=1; a=RSA-SHA256; d=example.com; s=s; c=relaxed; l=10; t=100; x=200;\r\n \
+             h=From : To:Subject:; bh=AAAA; b=AQID BA==",
+        )
+        .unwrap();
+        assert_eq!(signature.algorithm, "rsa-sha256");
+        assert_eq!(signature.key_type(), "rsa");
+        assert_eq!(signature.domain, "example.com");
+        assert_eq!(
+            signature.canonicalization,
+            (Canonicalization::Relaxed, Canonicalization::Simple)
+        );
+        assert_eq!(signature.signed_headers, ["From", "To", "Subject"]);
+        assert_eq!(signature.body_length, Some(10));
+        assert_eq!(
+            (signature.timestamp, signature.expiration),
+            (Some(100), Some(200))
+        );
+        assert_eq!(signature.signature, [1, 2, 3, 4]);
+        // Dropping the padding does not change the nullifier.
+        let unpadded =
+            DkimSignature::parse("a=rsa-sha256; d=example.com; h=from; b=AQIDBA").unwrap();
+        assert_eq!(unpadded.nullifier(), signature.nullifier());
+
+        assert_eq!(
+            DkimSignature::parse("a=rsa-sha256; d=example.com; b=AQID"),
+            None
+        );
+        assert_eq!(
+            DkimSignature::parse("a=rsa-sha256; d=example.com; h=from; c=bogus; b=AQID"),
+            None
+        );
+        assert_eq!(
+            DkimSignature::parse("a=rsa-sha256; d=example.com; h=from; l=-1; b=AQID"),
+            None
+        );
+    }
+
+    #[test]
+    fn canonicalizes_simple_body() {
+        let simple = |body: &[u8]| canonicalize_body(body, Canonicalization::Simple);
+        assert_eq!(simple(b""), b"\r\n");
+        assert_eq!(simple(b"\r\n\r\n"), b"\r\n");
+        assert_eq!(simple(b"a  b \r\n\r\n\r\n"), b"a  b \r\n");
+        assert_eq!(simple(b"a\r\n\r\nb"), b"a\r\n\r\nb\r\n");
+        assert_eq!(simple(b"a\nb\n"), b"a\r\nb\r\n");
+    }
+
+    #[test]
+    fn canonicalizes_relaxed_body() {
+        let relaxed = |body: &[u8]| canonicalize_body(body, Canonicalization::Relaxed);
+        assert_eq!(relaxed(b""), b"");
+        assert_eq!(relaxed(b" \r\n\t\r\n"), b"");
+        assert_eq!(relaxed(b"a \t b\t\r\n c \r\n\r\n"), b"a b\r\n c\r\n");
+        assert_eq!(relaxed(b"\xe2\x82\xb9  250\r\n"), b"\xe2\x82\xb9 250\r\n");
+    }
+
+    #[test]
+    fn truncates_body_to_length_tag() {
+        let email = format!(
+            "DKIM-Signature: a=rsa-sha256; d=example.com; h=from; l=6; b=AQID\r\n{}Added later\r\n",
+            MESSAGE
+        );
+        let signed = SignedContent::from_email(email.as_bytes(), "Example.COM").unwrap();
+        assert_eq!(signed.body, b"Body\r\n");
+
+        let email = format!(
+            "DKIM-Signature: a=rsa-sha256; d=example.com; h=from; l=2; b=AQID\r\n{}",
+            MESSAGE
+        );
+        let signed = SignedContent::from_email(email.as_bytes(), "example.com").unwrap();
+        assert_eq!(signed.body, b"Bo");
+    }
+
+    #[test]
+    fn selects_bottom_most_instances() {
+        let headers = [
+            header("Subject", "added"),
+            header("From", "a@example.com"),
+            header("Subject", "signed"),
+        ];
+        let signed = select_signed_headers(&headers, &["subject".to_string()]);
+        assert_eq!(signed, [header("Subject", "signed")]);
+
+        let names = ["subject".to_string(), "subject".to_string()];
+        let signed = select_signed_headers(&headers, &names);
+        assert_eq!(
+            signed,
+            [header("Subject", "signed"), header("Subject", "added")]
+        );
+    }
+
+    #[test]
+    fn over_signed_names_cover_nothing() {
+        let headers = [header("From", "a@example.com"), header("Subject", "signed")];
+        let names: Vec<String> = ["from", "subject", "subject", "to"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(
+            select_signed_headers(&headers, &names),
+            [header("From", "a@example.com"), header("Subject", "signed")]
+        );
+    }
+
+    #[test]
+    fn reads_signed_headers_only() {
+        let email = format!(
+            "{}Subject: Unsigned copy\r\n{}",
+            signature("example.com", "AQID"),
+            MESSAGE
+        );
+        let signed = SignedContent::from_email(email.as_bytes(), "example.com").unwrap();
+        assert_eq!(signed.header("subject"), Some("Paid"));
+        assert_eq!(signed.header("Date"), None);
+        assert_eq!(signed.body, b"Body\r\n");
+        assert_eq!(
+            SignedContent::from_email(email.as_bytes(), "example.org"),
+            None
+        );
+    }
+
+    #[test]
+    fn isolates_each_candidate_signature() {
+        let genuine = signature("example.com", "AQID");
+        let forged = signature("EXAMPLE.com", "BAUG");
+        let other = signature("example.org", "BwgJ");
+        let email = format!("{}{}{}{}", forged, other, genuine, MESSAGE);
+
+        let candidates = signature_candidates(email.as_bytes(), "example.com");
+        assert_eq!(candidates.len(), 2);
+        let (signed, isolated) = &candidates[0];
+        assert_eq!(signed.signature.signature, [4, 5, 6]);
+        assert_eq!(
+            isolated,
+            format!("{}{}{}", forged, other, MESSAGE).as_bytes()
+        );
+        let (signed, isolated) = &candidates[1];
+        assert_eq!(signed.signature.signature, [1, 2, 3]);
+        assert_eq!(
+            isolated,
+            format!("{}{}{}", other, genuine, MESSAGE).as_bytes()
+        );
+    }
+
+    #[test]
+    fn isolation_removes_signatures_that_do_not_parse() {
+        let genuine = signature("example.com", "AQID");
+        let malformed = "DKIM-Signature: a=rsa-sha256; d=example.com; c=bogus; b=BAUG\r\n";
+        let email = format!("{}{}{}", malformed, genuine, MESSAGE);
+
+        let candidates = signature_candidates(email.as_bytes(), "example.com");
+        assert_eq!(candidates.len(), 1);
+        let (signed, isolated) = &candidates[0];
+        assert_eq!(signed.signature.signature, [1, 2, 3]);
+        assert_eq!(isolated, format!("{}{}", genuine, MESSAGE).as_bytes());
+    }
+
+    #[test]
+    fn reads_content_from_the_signature_that_verifies() {
+        let email = format!(
+            "{}{}{}",
+            signature("example.com", "BAUG"),
+            signature("example.com", "AQID"),
+            MESSAGE
+        );
+        let verify = |signed: &SignedContent, _: &[u8]| match signed.signature.signature[0] {
+            1 => Ok(()),
+            other => Err(other),
+        };
+        let (signed, _) = verify_signed_content(
+            email.as_bytes(),
+            "example.com",
+            TimeWindow::default(),
+            verify,
+        )
+        .unwrap();
+        assert_eq!(signed.signature.signature, [1, 2, 3]);
+
+        let refuse = |signed: &SignedContent, _: &[u8]| Err(signed.signature.signature[0]);
+        assert_eq!(
+            verify_signed_content(
+                email.as_bytes(),
+                "example.com",
+                TimeWindow::default(),
+                refuse
+            ),
+            Err(VerifyError::Unverified(Some(4)))
+        );
+        assert_eq!(
+            verify_signed_content(
+                email.as_bytes(),
+                "example.org",
+                TimeWindow::default(),
+                verify
+            ),
+            Err(VerifyError::Unverified(None))
+        );
+    }
+
+    #[test]
+    fn refuses_verified_signatures_that_break_policy() {
+        let accept = |_: &SignedContent, _: &[u8]| Ok::<(), ()>(());
+
+        let unsigned_to = format!(
+            "DKIM-Signature: a=rsa-sha256; d=example.com; h=from:subject; b=AQID\r\n{}",
+            MESSAGE
+        );
+        let result = verify_signed_content(
+            unsigned_to.as_bytes(),
+            "example.com",
+            TimeWindow::default(),
+            accept,
+        );
+        assert_eq!(
+            result,
+            Err(VerifyError::Headers(ErrorCode::MissingSignedHeader))
+        );
+
+        let timed = |tags: &str| {
+            format!(
+                "DKIM-Signature: a=rsa-sha256; d=example.com; h=from:to:subject; {}; b=AQID\r\n{}",
+                tags, MESSAGE
+            )
+        };
+        let times = |signed_at, expires_at| Timestamps {
+            date: None,
+            signed_at: Some(signed_at),
+            expires_at,
+        };
+        let expired = timed("t=200; x=100");
+        assert_eq!(
+            verify_signed_content(
+                expired.as_bytes(),
+                "example.com",
+                TimeWindow::default(),
+                accept
+            ),
+            Err(VerifyError::ExpiredAtSigning(times(200, Some(100))))
+        );
+
+        let sent = timed("t=100");
+        let window = TimeWindow::new(Some(150), None);
+        assert_eq!(
+            verify_signed_content(sent.as_bytes(), "example.com", window, accept),
+            Err(VerifyError::OutsideWindow(times(100, None)))
+        );
+        let window = TimeWindow::new(Some(50), Some(150));
+        let (_, timestamps) =
+            verify_signed_content(sent.as_bytes(), "example.com", window, accept).unwrap();
+        assert_eq!(timestamps, times(100, None));
+    }
+
+    #[test]
+    fn splits_folded_headers_from_body() {
+        let email = b"Subject: a\r\n\tb\r\nFrom: c\r\n\r\nbody\r\n\r\nmore\r\n";
+        let (headers, body) = split_message(email);
+        assert_eq!(headers, [header("Subject", "a\tb"), header("From", "c")]);
+        assert_eq!(body, b"body\r\n\r\nmore\r\n");
+    }
+}
@@ -0,0 +1,87 @@
+//! Extraction of the payment details from a receipt email.
+//!
+//! This runs inside the program and, before proving, on the host, so both always agree on what an
+//! email contains. Only content covered by the DKIM signature is read, following the rules of a
+//! [`ReceiptTemplate`].
+
+use crate::amount::{parse_amount, parse_minor_units, Amount};
+use crate::dkim::SignedContent;
+use crate::mime::body_text;
+use crate::template::{Field, Transform};
+use crate::{ExtractedField, ExtractionStatus, ReceiptTemplate, Region};
+use regex::Regex;
+
+/// The payment details found in a receipt.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Payment {
+    pub receiver: ExtractedField,
+    /// The amount as written in the email.
+    pub amount_text: ExtractedField,
+    pub sender: ExtractedField,
+    pub reference: ExtractedField,
+    pub amount: Amount,
+}
+
+/// Applies each of the template's rules to the signed content.
+///
+/// Every rule must match. An amount without a currency marker is read in the template's
+/// currency.
+pub fn extract_payment(
+    signed: &SignedContent,
+    template: &ReceiptTemplate,
+) -> Result<Payment, ExtractionStatus> {
+    // Without a sender domain any signer could produce the receipt, so a contract trusting the
+    // template hash would trust every domain.
+    if template.sender_domain.is_empty() {
+        return Err(ExtractionStatus::InvalidTemplate);
+    }
+    if !template.accepts_domain(&signed.signature.domain) {
+        return Err(ExtractionStatus::DomainMismatch);
+    }
+
+    let body = body_text(signed);
+    let mut payment = Payment::default();
+    for rule in &template.rules {
+        let field = Field::try_from(rule.field).map_err(|_| ExtractionStatus::InvalidTemplate)?;
+        let transform =
+            Transform::try_from(rule.transform).map_err(|_| ExtractionStatus::InvalidTemplate)?;
+        let region =
+            Region::try_from(rule.source).map_err(|_| ExtractionStatus::InvalidTemplate)?;
+        let text = match region {
+            Region::Body => body.as_str(),
+            Region::Header => signed
+                .header(&rule.header)
+                .ok_or(ExtractionStatus::NotFound)?,
+            Region::NotFound => return Err(ExtractionStatus::InvalidTemplate),
+        };
+
+        let re = Regex::new(&rule.pattern).map_err(|_| ExtractionStatus::InvalidTemplate)?;
+        let captures = re.captures(text).ok_or(ExtractionStatus::NotFound)?;
+        let capture = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .ok_or(ExtractionStatus::NotFound)?;
+        let extracted = ExtractedField {
+            value: transform.apply(capture.as_str()),
+            region: region as u8,
+            ..Default::default()
+        };
+
+        match field {
+            Field::Receiver => payment.receiver = extracted,
+            Field::Amount => payment.amount_text = extracted,
+            Field::Sender => payment.sender = extracted,
+            Field::Reference => payment.reference = extracted,
+        }
+    }
+
+    payment.amount = parse_amount(&payment.amount_text.value)
+        .or_else(|| {
+            Some(Amount {
+                minor_units: parse_minor_units(payment.amount_text.value.trim())?,
+                currency: template.currency.0,
+            })
+        })
+        .ok_or(ExtractionStatus::NotFound)?;
+    Ok(payment)
+}
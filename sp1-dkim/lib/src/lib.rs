@@ -1,13 +1,364 @@
 use alloy_sol_types::sol;
 
+pub mod account;
+pub mod alignment;
+pub mod amount;
+pub mod command;
+pub mod dkim;
+pub mod extract;
+pub mod mime;
+pub mod policy;
+pub mod privacy;
+pub mod registration;
+pub mod template;
+pub mod templates;
+pub mod time;
+
+pub use alignment::Alignment;
+pub use amount::{parse_amount, Amount};
+pub use command::{parse_command, Command, CommandKind, Recipient, RecipientKind, DEFAULT_RELAYER};
+pub use extract::{extract_payment, Payment};
+pub use privacy::{field_commitment, FieldSalts};
+pub use registration::{parse_registration, Registration};
+pub use templates::{builtin_templates, find_template, DEFAULT_TEMPLATE};
+pub use time::{TimeWindow, Timestamps};
+
 sol! {
+    /// A value read from the email, with the signed region it was found in.
+    ///
+    /// In a private receipt the value is left empty and only its salted `hash` is committed;
+    /// otherwise the hash is zero.
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct ExtractedField {
+        string value;
+        uint8 region;
+        bytes32 hash;
+    }
+
+    /// How one payment field is found in the signed content.
+    #[derive(Debug, PartialEq, Eq)]
+    struct FieldRule {
+        uint8 field;
+        uint8 source;
+        string header;
+        string pattern;
+        uint8 transform;
+    }
+
+    /// A receipt format. Its hash is committed so contracts can allow-list formats.
+    #[derive(Debug, PartialEq, Eq)]
+    struct ReceiptTemplate {
+        string id;
+        string sender_domain;
+        bytes3 currency;
+        FieldRule[] rules;
+    }
+
     /// The public values encoded as a struct that can be easily deserialized inside Solidity.
+    #[derive(Default)]
     struct PublicValuesStruct {
+        uint8 mode;
+        bytes32 from_domain_hash;
+        bytes32 public_key_hash;
+        bytes32 template_hash;
+        bytes32 nullifier;
+        bool result;
+        uint8 error_code;
+        uint8 from_alignment;
+        uint8 extraction_status;
+        ExtractedField receiver;
+        ExtractedField amount_text;
+        ExtractedField sender;
+        ExtractedField reference;
+        uint256 amount;
+        bytes3 currency;
+        uint64 date;
+        uint64 dkim_timestamp;
+        uint64 dkim_expiration;
+        uint64 not_before;
+        uint64 not_after;
+        address claimant;
+        uint256 chain_id;
+        address contract_address;
+    }
+
+    /// The public values of a command email, such as "Send 0.1 PYUSD to recipient@example.com".
+    #[derive(Default)]
+    struct CommandValuesStruct {
+        uint8 mode;
+        bytes32 from_domain_hash;
+        bytes32 public_key_hash;
+        bytes32 nullifier;
+        bool result;
+        uint8 error_code;
+        uint8 from_alignment;
+        bytes32 account_hash;
+        bytes32 relayer_hash;
+        uint8 command;
+        uint256 amount;
+        uint8 decimals;
+        string token;
+        uint8 recipient_kind;
+        string recipient;
+        address recipient_address;
+        uint64 date;
+        uint64 dkim_timestamp;
+        uint64 dkim_expiration;
+        uint64 not_before;
+        uint64 not_after;
+        address claimant;
+        uint256 chain_id;
+        address contract_address;
+    }
+
+    /// The public values of a registration reply, linking the sender's account to a wallet.
+    #[derive(Default)]
+    struct RegistrationValuesStruct {
+        uint8 mode;
         bytes32 from_domain_hash;
         bytes32 public_key_hash;
+        bytes32 nullifier;
         bool result;
-        string receiver;
-        string amount;
-        string sender;
+        uint8 error_code;
+        uint8 from_alignment;
+        bytes32 account_hash;
+        bytes32 relayer_hash;
+        address wallet;
+        bytes32 nonce;
+        uint64 date;
+        uint64 dkim_timestamp;
+        uint64 dkim_expiration;
+        uint64 not_before;
+        uint64 not_after;
+        address claimant;
+        uint256 chain_id;
+        address contract_address;
+    }
+}
+
+/// What the program proves about an email, read as its first input and committed as `mode`.
+///
+/// Each mode commits its own struct, and all of them start with `mode` so that a contract can
+/// check it is reading the layout it expects.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailMode {
+    /// A payment receipt, read with a template into a [`PublicValuesStruct`].
+    Receipt = 0,
+    /// A command in the subject, parsed into a [`CommandValuesStruct`].
+    Command = 1,
+    /// A reply to a registration challenge, parsed into a [`RegistrationValuesStruct`].
+    Registration = 2,
+    /// A payment receipt like [`EmailMode::Receipt`], with the receiver and sender committed only
+    /// as the salted hashes in their `hash`.
+    PrivateReceipt = 3,
+}
+
+impl TryFrom<u8> for EmailMode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Receipt),
+            1 => Ok(Self::Command),
+            2 => Ok(Self::Registration),
+            3 => Ok(Self::PrivateReceipt),
+            other => Err(other),
+        }
+    }
+}
+
+/// Reads the mode of ABI-encoded public values without decoding the rest.
+///
+/// The receipt and command structs are dynamic, so their encoding starts with a 32-byte offset
+/// followed by the `mode` word. The registration struct is static and starts with `mode` itself;
+/// no mode is as large as the offset, so the two cannot be confused.
+pub fn committed_mode(public_values: &[u8]) -> Option<EmailMode> {
+    let first = public_values.get(..32)?;
+    let is_offset = first[..31].iter().all(|&b| b == 0) && first[31] == 32;
+    let word = match is_offset {
+        true => public_values.get(32..64)?,
+        false => first,
+    };
+    if word[..31].iter().any(|&b| b != 0) {
+        return None;
+    }
+    EmailMode::try_from(word[31]).ok()
+}
+
+/// The first check an email failed, committed as `error_code`.
+///
+/// `result` says whether the email itself passed every check. It can be true alongside
+/// [`ErrorCode::InvalidTemplate`], [`ErrorCode::ExtractionMiss`] or any later code other than
+/// [`ErrorCode::UnknownMode`], which only concern what is read out of the email.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoError = 0,
+    /// `From`, `To` or `Subject` is not listed in the signature's `h=` tag.
+    MissingSignedHeader = 1,
+    /// `From`, `To` or `Subject` appears more than once in the message.
+    DuplicateHeader = 2,
+    /// A header listed in `h=` has more instances in the message than `h=` covers.
+    UnsignedHeaderInstance = 3,
+    /// The email, or its `DKIM-Signature` from the domain, could not be parsed.
+    ParseError = 4,
+    /// The public key is not of the type the signature's `a=` algorithm needs.
+    KeyTypeMismatch = 5,
+    /// The body does not hash to the signature's `bh=`.
+    BodyHashMismatch = 6,
+    /// The signature does not verify against the public key.
+    SignatureInvalid = 7,
+    /// The signature's `x=` is not after its `t=`, or it has expired.
+    SignatureExpired = 8,
+    /// The email was not sent within the window the proof is bound to.
+    OutsideWindow = 9,
+    /// DKIM verification failed for another reason.
+    DkimFailure = 10,
+    /// The template could not be decoded.
+    InvalidTemplate = 11,
+    /// The template's fields were not found; `extraction_status` says why.
+    ExtractionMiss = 12,
+    /// The signed subject is not a valid command.
+    CommandMiss = 13,
+    /// The relayer is not in the signed `To` or `Cc` header of a command or registration email.
+    NotAddressedToRelayer = 14,
+    /// The signed subject is not a reply to a registration challenge.
+    RegistrationMiss = 15,
+    /// The registration reply is for a different challenge nonce.
+    NonceMismatch = 16,
+    /// The mode read by the program is not an [`EmailMode`]. Nothing else is checked and the
+    /// public values use the receipt layout.
+    UnknownMode = 17,
+    /// The domain of the signed From address of a command or registration email is not the
+    /// signing domain, so the alignment is not [`Alignment::Strict`].
+    UnalignedFrom = 18,
+}
+
+impl ErrorCode {
+    /// A readable explanation of the code.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::NoError => "the email was verified and the payment extracted",
+            Self::MissingSignedHeader => {
+                "the signature does not cover From, To and Subject, so they could be rewritten"
+            }
+            Self::DuplicateHeader => "From, To or Subject appears more than once in the email",
+            Self::UnsignedHeaderInstance => {
+                "a signed header has an extra, unsigned instance that was added after signing"
+            }
+            Self::ParseError => "the email or its DKIM-Signature header could not be parsed",
+            Self::KeyTypeMismatch => {
+                "the public key is not of the type the signature's algorithm needs"
+            }
+            Self::BodyHashMismatch => "body hash mismatch: the body was changed after signing",
+            Self::SignatureInvalid => {
+                "signature invalid: the signed headers were changed or the key does not belong \
+                 to this selector"
+            }
+            Self::SignatureExpired => "the signature expired, or expires before it was made",
+            Self::OutsideWindow => "the email was not sent within --not-before/--not-after",
+            Self::DkimFailure => "DKIM verification failed",
+            Self::InvalidTemplate => "the receipt template could not be decoded",
+            Self::ExtractionMiss => "the template's fields were not found in the signed content",
+            Self::CommandMiss => "the signed Subject is not a valid command",
+            Self::NotAddressedToRelayer => {
+                "the email was not sent to the relayer in a signed To or Cc header"
+            }
+            Self::RegistrationMiss => "the signed Subject is not a registration challenge reply",
+            Self::NonceMismatch => "the registration reply is for a different challenge nonce",
+            Self::UnknownMode => "the program was given a mode it does not know",
+            Self::UnalignedFrom => {
+                "the signed From address is not in the signing domain itself, so it does not name \
+                 the sender"
+            }
+        }
+    }
+}
+
+impl TryFrom<u8> for ErrorCode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::NoError),
+            1 => Ok(Self::MissingSignedHeader),
+            2 => Ok(Self::DuplicateHeader),
+            3 => Ok(Self::UnsignedHeaderInstance),
+            4 => Ok(Self::ParseError),
+            5 => Ok(Self::KeyTypeMismatch),
+            6 => Ok(Self::BodyHashMismatch),
+            7 => Ok(Self::SignatureInvalid),
+            8 => Ok(Self::SignatureExpired),
+            9 => Ok(Self::OutsideWindow),
+            10 => Ok(Self::DkimFailure),
+            11 => Ok(Self::InvalidTemplate),
+            12 => Ok(Self::ExtractionMiss),
+            13 => Ok(Self::CommandMiss),
+            14 => Ok(Self::NotAddressedToRelayer),
+            15 => Ok(Self::RegistrationMiss),
+            16 => Ok(Self::NonceMismatch),
+            17 => Ok(Self::UnknownMode),
+            18 => Ok(Self::UnalignedFrom),
+            other => Err(other),
+        }
+    }
+}
+
+/// Whether the payment fields could be extracted from the email.
+///
+/// Committed as `extraction_status` so that a verifier can tell a DKIM-valid email that simply
+/// isn't a payment receipt apart from one that is.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractionStatus {
+    /// Receiver, amount and sender were all found, and the amount parsed.
+    Extracted = 0,
+    /// The email did not match the receipt format or the amount could not be parsed; the payment
+    /// fields are empty.
+    NotFound = 1,
+    /// The email is not signed by the template's sender domain.
+    DomainMismatch = 2,
+    /// The template has no sender domain, an unknown field, source or transform, or a pattern
+    /// that doesn't compile.
+    InvalidTemplate = 3,
+}
+
+impl TryFrom<u8> for ExtractionStatus {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Extracted),
+            1 => Ok(Self::NotFound),
+            2 => Ok(Self::DomainMismatch),
+            3 => Ok(Self::InvalidTemplate),
+            other => Err(other),
+        }
+    }
+}
+
+/// The part of the DKIM-signed content an [`ExtractedField`] was read from.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// The field was not found; its value is empty.
+    NotFound = 0,
+    /// A header listed in the signature's `h=` tag.
+    Header = 1,
+    /// The canonicalised body, up to the signature's `l=` tag.
+    Body = 2,
+}
+
+impl TryFrom<u8> for Region {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::NotFound),
+            1 => Ok(Self::Header),
+            2 => Ok(Self::Body),
+            other => Err(other),
+        }
     }
 }
@@ -0,0 +1,246 @@
+//! Turning the signed body into plain text.
+//!
+//! Receipts are usually `multipart/alternative` with quoted-printable or base64 HTML parts, so the
+//! signed body is parsed as MIME, each text part is decoded, and HTML is flattened to text before
+//! any pattern is matched against it.
+
+use crate::dkim::SignedContent;
+use mailparse::{parse_mail, ParsedMail};
+
+/// The headers that decide how the top-level body is decoded.
+const MIME_HEADERS: [&str; 2] = ["Content-Type", "Content-Transfer-Encoding"];
+
+/// Decodes the signed body into text.
+///
+/// Only signed MIME headers are used; if `Content-Type` is not signed, the body is read as plain
+/// text, since an unsigned header could otherwise change how the signed bytes are interpreted.
+pub fn body_text(signed: &SignedContent) -> String {
+    let mut message = Vec::new();
+    for name in MIME_HEADERS {
+        if let Some(value) = signed.header(name) {
+            message.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+    }
+    if message.is_empty() {
+        return String::from_utf8_lossy(&signed.body).into_owned();
+    }
+    message.extend_from_slice(b"\r\n");
+    message.extend_from_slice(&signed.body);
+
+    match parse_mail(&message) {
+        Ok(mail) => {
+            let mut text = String::new();
+            collect_text(&mail, &mut text);
+            text
+        }
+        Err(_) => String::from_utf8_lossy(&signed.body).into_owned(),
+    }
+}
+
+/// Appends the decoded text of every `text/*` part, depth first.
+fn collect_text(part: &ParsedMail, text: &mut String) {
+    if !part.subparts.is_empty() {
+        for subpart in &part.subparts {
+            collect_text(subpart, text);
+        }
+        return;
+    }
+
+    let mimetype = part.ctype.mimetype.to_lowercase();
+    if !mimetype.starts_with("text/") {
+        return;
+    }
+    let body = match part.get_body() {
+        Ok(body) => body,
+        Err(_) => return,
+    };
+    if mimetype == "text/html" {
+        text.push_str(&html_to_text(&body));
+    } else {
+        text.push_str(&body);
+    }
+    text.push('\n');
+}
+
+/// Elements that start a new line when rendered.
+const BLOCK_TAGS: [&str; 12] = [
+    "br", "p", "div", "tr", "li", "table", "h1", "h2", "h3", "h4", "td", "th",
+];
+
+/// Strips tags from HTML, keeping line structure and decoding entities.
+///
+/// Block-level elements and table cells become line breaks, and the contents of `<script>` and
+/// `<style>` are dropped.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut skip_until: Option<&str> = None;
+
+    while let Some(start) = rest.find('<') {
+        if skip_until.is_none() {
+            text.push_str(&decode_entities(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+
+        if let Some(until) = skip_until {
+            if closing && name == until {
+                skip_until = None;
+            }
+            continue;
+        }
+        match name.as_str() {
+            "script" if !closing => skip_until = Some("script"),
+            "style" if !closing => skip_until = Some("style"),
+            "head" if !closing => skip_until = Some("head"),
+            name if BLOCK_TAGS.contains(&name) => text.push('\n'),
+            _ => {}
+        }
+    }
+    if skip_until.is_none() {
+        text.push_str(&decode_entities(rest));
+    }
+
+    // Collapse the whitespace HTML does not render, keeping one line per block.
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decodes named and numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "rupee" => Some('₹'),
+            _ => {
+                let code = match entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dkim::{DkimSignature, Header};
+
+    fn signed(headers: &[(&str, &str)], body: &str) -> SignedContent {
+        SignedContent {
+            signature: DkimSignature::parse("a=rsa-sha256; d=example.com; h=from; b=AQID").unwrap(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| Header {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn reads_plain_body_without_signed_content_type() {
+        let body = "Paid =E2=82=B9 250\r\n";
+        assert_eq!(body_text(&signed(&[], body)), body);
+    }
+
+    #[test]
+    fn decodes_quoted_printable() {
+        let signed = signed(
+            &[
+                ("Content-Type", "text/plain; charset=utf-8"),
+                ("Content-Transfer-Encoding", "quoted-printable"),
+            ],
+            "Paid =E2=82=B9 250 to ramesh.kumar@ybl, a very long line that was soft=\r\n-wrapped\r\n",
+        );
+        assert_eq!(
+            body_text(&signed),
+            "Paid ₹ 250 to ramesh.kumar@ybl, a very long line that was soft-wrapped\r\n\n"
+        );
+    }
+
+    #[test]
+    fn decodes_base64_html_alternative() {
+        let signed = signed(
+            &[("Content-Type", "multipart/alternative; boundary=\"b\"")],
+            "--b\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             Plain part\r\n\
+             --b\r\n\
+             Content-Type: text/html\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n\
+             PHA+UGFpZCAmIzgzNzc7MjUwPC9wPjx0YWJsZT48dHI+PHRkPlRvPC90ZD48dGQ+YUB5Ymw8L3Rk\r\n\
+             PjwvdHI+PC90YWJsZT4=\r\n\
+             --b\r\n\
+             Content-Type: image/png\r\n\r\n\
+             not text\r\n\
+             --b--\r\n",
+        );
+        assert_eq!(body_text(&signed), "Plain part\r\n\nPaid ₹250\nTo\na@ybl\n");
+    }
+
+    #[test]
+    fn flattens_html() {
+        assert_eq!(
+            html_to_text(
+                "<html><head><title>Receipt</title></head><body>\
+                 <style>p { color: red }</style><script>alert('<p>')</script>\
+                 <p>Paid&nbsp;&rupee;&#x20;1,500</p><div>To \t<b>ankit</b>  sharma</div>\
+                 <br/>Ref &amp; UTR &lt;4288&gt; &bogus; & done</body></html>"
+            ),
+            "Paid ₹ 1,500\nTo ankit sharma\nRef & UTR <4288> &bogus; & done"
+        );
+    }
+
+    #[test]
+    fn drops_unterminated_tags() {
+        assert_eq!(html_to_text("Paid <b>250</b> <span"), "Paid 250");
+    }
+}
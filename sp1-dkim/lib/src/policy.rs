@@ -0,0 +1,120 @@
+//! Which headers a DKIM signature has to cover before its email is accepted.
+//!
+//! A signature that leaves out `From`, `To` or `Subject` lets anyone rewrite them, and a header
+//! with more instances in the message than `h=` lists has an unsigned copy that many mail clients
+//! display in place of the signed one.
+
+use crate::dkim::{DkimSignature, Header};
+use crate::ErrorCode;
+
+/// Headers that must be listed in `h=` and appear at most once in the message.
+pub const REQUIRED_SIGNED_HEADERS: [&str; 3] = ["From", "To", "Subject"];
+
+/// Checks the message's headers against the signature's `h=` list.
+pub fn check_signed_headers(
+    headers: &[Header],
+    signature: &DkimSignature,
+) -> Result<(), ErrorCode> {
+    let count_in_message = |name: &str| {
+        headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .count()
+    };
+    let count_signed = |name: &str| {
+        signature
+            .signed_headers
+            .iter()
+            .filter(|h| h.eq_ignore_ascii_case(name))
+            .count()
+    };
+
+    for name in REQUIRED_SIGNED_HEADERS {
+        if count_signed(name) == 0 {
+            return Err(ErrorCode::MissingSignedHeader);
+        }
+        if count_in_message(name) > 1 {
+            return Err(ErrorCode::DuplicateHeader);
+        }
+    }
+
+    // Over-signing lists a header once more than it occurs so that no instance can be added. A
+    // header that occurs more often than it is listed has an instance the signature leaves out.
+    for name in &signature.signed_headers {
+        if count_in_message(name) > count_signed(name) {
+            return Err(ErrorCode::UnsignedHeaderInstance);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<Header> {
+        names
+            .iter()
+            .map(|name| Header {
+                name: name.to_string(),
+                value: String::new(),
+            })
+            .collect()
+    }
+
+    fn signing(h: &str) -> DkimSignature {
+        DkimSignature::parse(&format!("a=rsa-sha256; d=example.com; h={}; b=AQID", h)).unwrap()
+    }
+
+    const MESSAGE: [&str; 5] = ["DKIM-Signature", "Date", "From", "To", "Subject"];
+
+    #[test]
+    fn accepts_signed_critical_headers() {
+        let check = |h| check_signed_headers(&headers(&MESSAGE), &signing(h));
+        assert_eq!(check("from:to:subject"), Ok(()));
+        assert_eq!(check("Date:FROM:To:subject"), Ok(()));
+        // Over-signing lists a header more often than it occurs.
+        assert_eq!(check("from:from:to:to:subject:subject:date:date"), Ok(()));
+    }
+
+    #[test]
+    fn requires_from_to_and_subject_in_h() {
+        let check = |h| check_signed_headers(&headers(&MESSAGE), &signing(h));
+        assert_eq!(check("to:subject"), Err(ErrorCode::MissingSignedHeader));
+        assert_eq!(check("from:subject"), Err(ErrorCode::MissingSignedHeader));
+        assert_eq!(check("from:to:date"), Err(ErrorCode::MissingSignedHeader));
+    }
+
+    #[test]
+    fn refuses_duplicate_critical_headers() {
+        let message = headers(&["From", "From", "To", "Subject"]);
+        assert_eq!(
+            check_signed_headers(&message, &signing("from:to:subject")),
+            Err(ErrorCode::DuplicateHeader)
+        );
+        // Signing both copies does not make a second From acceptable.
+        assert_eq!(
+            check_signed_headers(&message, &signing("from:from:to:subject")),
+            Err(ErrorCode::DuplicateHeader)
+        );
+    }
+
+    #[test]
+    fn refuses_unsigned_instances_of_signed_headers() {
+        let message = headers(&["Date", "Date", "From", "To", "Subject"]);
+        assert_eq!(
+            check_signed_headers(&message, &signing("date:from:to:subject")),
+            Err(ErrorCode::UnsignedHeaderInstance)
+        );
+        assert_eq!(
+            check_signed_headers(&message, &signing("date:date:from:to:subject")),
+            Ok(())
+        );
+        // Headers outside h= may repeat.
+        let message = headers(&["Received", "Received", "From", "To", "Subject"]);
+        assert_eq!(
+            check_signed_headers(&message, &signing("from:to:subject")),
+            Ok(())
+        );
+    }
+}
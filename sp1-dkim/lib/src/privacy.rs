@@ -0,0 +1,23 @@
+//! Salted commitments to the receipt fields that identify a bank account.
+//!
+//! In a private receipt the receiver and sender are committed as `sha256(value || salt)` instead
+//! of in clear text. The salts are private inputs, so the hashes cannot be reversed by guessing
+//! account numbers; the user keeps each value and salt as an opening and can reveal one field to
+//! a counterparty without revealing the other.
+
+use sha2::{Digest, Sha256};
+
+/// The salts of the fields committed in a private receipt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FieldSalts {
+    pub receiver: [u8; 32],
+    pub sender: [u8; 32],
+}
+
+/// SHA-256 of the extracted value followed by the salt.
+pub fn field_commitment(value: &str, salt: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(value.as_bytes());
+    hasher.update(salt);
+    hasher.finalize().into()
+}
@@ -0,0 +1,97 @@
+//! Registration replies, which link the sender's email address to a wallet.
+//!
+//! The name registry emails a challenge whose subject names the wallet and a fresh nonce:
+//!
+//! ```text
+//! Register 0x000000000000000000000000000000000000dEaD nonce 0x<64 hex digits>
+//! ```
+//!
+//! The owner of the address agrees to the link by replying to it. Any `Re:` prefixes the mail
+//! client adds are ignored, and keywords are case-insensitive.
+
+use crate::command::{parse_hex, signed_subject, to_hex};
+use crate::dkim::SignedContent;
+
+/// A wallet the sender agreed to link their address to, in reply to a challenge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Registration {
+    pub wallet: [u8; 20],
+    pub nonce: [u8; 32],
+}
+
+/// The subject of the challenge email for a wallet and nonce.
+pub fn challenge_subject(wallet: &[u8; 20], nonce: &[u8; 32]) -> String {
+    format!("Register {} nonce {}", to_hex(wallet), to_hex(nonce))
+}
+
+/// Parses the registration in the bottom-most signed `Subject` header.
+pub fn signed_registration(signed: &SignedContent) -> Option<Registration> {
+    parse_registration(&signed_subject(signed)?)
+}
+
+/// Parses a challenge subject, or the subject of a reply to one.
+pub fn parse_registration(text: &str) -> Option<Registration> {
+    let mut text = text.trim();
+    while let Some(rest) = text.get(..3).filter(|p| p.eq_ignore_ascii_case("re:")) {
+        text = text[rest.len()..].trim_start();
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let [verb, wallet, keyword, nonce] = words.as_slice() else {
+        return None;
+    };
+    if !verb.eq_ignore_ascii_case("register") || !keyword.eq_ignore_ascii_case("nonce") {
+        return None;
+    }
+    Some(Registration {
+        wallet: parse_hex(strip_0x(wallet)?)?,
+        nonce: parse_hex(strip_0x(nonce)?)?,
+    })
+}
+
+fn strip_0x(text: &str) -> Option<&str> {
+    text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: [u8; 20] = [0xab; 20];
+    const NONCE: [u8; 32] = [0x01; 32];
+
+    #[test]
+    fn parses_challenge_and_replies() {
+        let subject = challenge_subject(&WALLET, &NONCE);
+        let expected = Some(Registration {
+            wallet: WALLET,
+            nonce: NONCE,
+        });
+        assert_eq!(parse_registration(&subject), expected);
+        assert_eq!(parse_registration(&format!("Re: {}", subject)), expected);
+        assert_eq!(parse_registration(&format!("RE:Re: {}", subject)), expected);
+        assert_eq!(
+            parse_registration(&subject.to_uppercase().replace("0X", "0x")),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_replies() {
+        let wallet = to_hex(&WALLET);
+        let nonce = to_hex(&NONCE);
+        for text in [
+            String::new(),
+            format!("Fwd: Register {} nonce {}", wallet, nonce),
+            format!("Register {} {}", wallet, nonce),
+            format!("Register {} nonce {} please", wallet, nonce),
+            format!("Register {} nonce {}", &wallet[2..], nonce),
+            format!("Register {} nonce {}", wallet, &nonce[2..]),
+            format!("Register {}00 nonce {}", wallet, nonce),
+            format!("Register {} nonce {}", wallet, &nonce[..64]),
+            format!("Link {} nonce {}", wallet, nonce),
+        ] {
+            assert_eq!(parse_registration(&text), None, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Receipt templates.
+//!
+//! A template describes one receipt format: the domain that signs it and, for each payment field,
+//! where to look and the pattern that captures it. The host picks a template by ID and passes it
+//! to the program, which commits the template hash so contracts can allow-list formats without a
+//! new program or verifying key per bank.
+
+use crate::{FieldRule, ReceiptTemplate, Region};
+use alloy_sol_types::SolType;
+use sha2::{Digest, Sha256};
+
+/// The payment field a [`FieldRule`] fills in.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// The payee, usually a UPI VPA.
+    Receiver = 0,
+    Amount = 1,
+    /// The payer's (masked) debit account.
+    Sender = 2,
+    /// The transaction reference, such as a UPI reference number.
+    Reference = 3,
+}
+
+impl TryFrom<u8> for Field {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Receiver),
+            1 => Ok(Self::Amount),
+            2 => Ok(Self::Sender),
+            3 => Ok(Self::Reference),
+            other => Err(other),
+        }
+    }
+}
+
+/// Post-processing applied to a captured value.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Keep the capture as it is.
+    None = 0,
+    /// Trim and collapse runs of whitespace to a single space.
+    CollapseWhitespace = 1,
+    /// Remove all whitespace and uppercase, for account and reference numbers.
+    Identifier = 2,
+    /// Trim and lowercase, for addresses and VPAs.
+    Lowercase = 3,
+}
+
+impl TryFrom<u8> for Transform {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::CollapseWhitespace),
+            2 => Ok(Self::Identifier),
+            3 => Ok(Self::Lowercase),
+            other => Err(other),
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Transform::None => value.to_string(),
+            Transform::CollapseWhitespace => value.split_whitespace().collect::<Vec<_>>().join(" "),
+            Transform::Identifier => value
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_uppercase(),
+            Transform::Lowercase => value.trim().to_lowercase(),
+        }
+    }
+}
+
+impl FieldRule {
+    /// A rule matching `pattern` against the decoded signed body.
+    pub fn body(field: Field, pattern: &str, transform: Transform) -> Self {
+        Self {
+            field: field as u8,
+            source: Region::Body as u8,
+            header: String::new(),
+            pattern: pattern.to_string(),
+            transform: transform as u8,
+        }
+    }
+
+    /// A rule matching `pattern` against the signed instance of `header`.
+    pub fn header(field: Field, header: &str, pattern: &str, transform: Transform) -> Self {
+        Self {
+            field: field as u8,
+            source: Region::Header as u8,
+            header: header.to_string(),
+            pattern: pattern.to_string(),
+            transform: transform as u8,
+        }
+    }
+}
+
+impl ReceiptTemplate {
+    /// The hash committed by the program: SHA-256 of the ABI-encoded template.
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(ReceiptTemplate::abi_encode(self)).into()
+    }
+
+    /// Whether `domain`, a DKIM `d=` tag, is the template's sender domain or a subdomain of it.
+    ///
+    /// A template without a sender domain accepts no signer; [`extract_payment`] refuses it as
+    /// invalid.
+    ///
+    /// [`extract_payment`]: crate::extract_payment
+    pub fn accepts_domain(&self, domain: &str) -> bool {
+        if self.sender_domain.is_empty() {
+            return false;
+        }
+        let domain = domain.to_lowercase();
+        let sender_domain = self.sender_domain.to_lowercase();
+        domain == sender_domain || domain.ends_with(&format!(".{}", sender_domain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(sender_domain: &str) -> ReceiptTemplate {
+        ReceiptTemplate {
+            id: "test".to_string(),
+            sender_domain: sender_domain.to_string(),
+            currency: (*b"INR").into(),
+            rules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn accepts_sender_domain_and_subdomains() {
+        let template = template("PhonePe.com");
+        assert!(template.accepts_domain("phonepe.com"));
+        assert!(template.accepts_domain("mail.PHONEPE.com"));
+        assert!(!template.accepts_domain("evilphonepe.com"));
+        assert!(!template.accepts_domain("phonepe.com.evil.example"));
+        assert!(!template.accepts_domain("com"));
+    }
+
+    #[test]
+    fn empty_sender_domain_accepts_nobody() {
+        assert!(!template("").accepts_domain("evil.example"));
+        assert!(!template("").accepts_domain(""));
+    }
+}
@@ -0,0 +1,221 @@
+//! The receipt templates built into the script.
+
+use crate::ReceiptTemplate;
+
+mod upi;
+mod usd;
+
+/// The template used when none is chosen.
+pub const DEFAULT_TEMPLATE: &str = "upi-paid-to";
+
+/// Every built-in template.
+pub fn builtin_templates() -> Vec<ReceiptTemplate> {
+    vec![
+        upi::paid_to(),
+        upi::phonepe(),
+        upi::gpay(),
+        upi::paytm(),
+        upi::hdfc(),
+        upi::icici(),
+        upi::sbi(),
+        usd::paypal(),
+        usd::paypal_pyusd(),
+        usd::venmo(),
+        usd::cashapp(),
+    ]
+}
+
+/// Looks up a built-in template by ID.
+pub fn find_template(id: &str) -> Option<ReceiptTemplate> {
+    builtin_templates().into_iter().find(|t| t.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dkim::SignedContent;
+    use crate::{extract_payment, ExtractionStatus};
+    use std::path::Path;
+
+    /// A sample receipt in `fixtures/emails`, with its signing domain and what each template reads
+    /// out of it.
+    struct Fixture {
+        id: &'static str,
+        domain: &'static str,
+        receiver: &'static str,
+        amount_text: &'static str,
+        minor_units: u128,
+        currency: &'static [u8; 3],
+        sender: &'static str,
+        reference: &'static str,
+    }
+
+    const FIXTURES: [Fixture; 10] = [
+        Fixture {
+            id: "upi-phonepe",
+            domain: "phonepe.com",
+            receiver: "ramesh.kumar@ybl",
+            amount_text: "₹ 250.00",
+            minor_units: 25000,
+            currency: b"INR",
+            sender: "XXXXXXXX1234",
+            reference: "428912345678",
+        },
+        Fixture {
+            id: "upi-gpay",
+            domain: "google.com",
+            receiver: "ankit.sharma@okaxis",
+            amount_text: "₹1,500.00",
+            minor_units: 150000,
+            currency: b"INR",
+            sender: "XXXX5678",
+            reference: "428812345670",
+        },
+        Fixture {
+            id: "upi-paytm",
+            domain: "paytm.com",
+            receiver: "priyastores@paytm",
+            amount_text: "₹ 1,200",
+            minor_units: 120000,
+            currency: b"INR",
+            sender: "XXXXXX9012",
+            reference: "428712345671",
+        },
+        Fixture {
+            id: "upi-hdfc",
+            domain: "hdfcbank.net",
+            receiver: "cafe.coffeeday@okhdfcbank",
+            amount_text: "Rs.750.00",
+            minor_units: 75000,
+            currency: b"INR",
+            sender: "**4321",
+            reference: "428612345672",
+        },
+        Fixture {
+            id: "upi-icici",
+            domain: "icicibank.com",
+            receiver: "zomato@icici",
+            amount_text: "INR 1,499.00",
+            minor_units: 149900,
+            currency: b"INR",
+            sender: "XX345",
+            reference: "428512345673",
+        },
+        Fixture {
+            id: "upi-sbi",
+            domain: "alerts.sbi.co.in",
+            receiver: "sharmakirana@sbi",
+            amount_text: "300.00",
+            minor_units: 30000,
+            currency: b"INR",
+            sender: "XXXXX6789",
+            reference: "428412345674",
+        },
+        Fixture {
+            id: "paypal",
+            domain: "paypal.com",
+            receiver: "jane.doe@example.com",
+            amount_text: "$45.00",
+            minor_units: 4500,
+            currency: b"USD",
+            sender: "sam.carter@example.org",
+            reference: "8AB12345CD678901E",
+        },
+        Fixture {
+            id: "paypal-pyusd",
+            domain: "paypal.com",
+            receiver: "alex.crypto@example.com",
+            amount_text: "25.50 PYUSD",
+            minor_units: 2550,
+            currency: b"USD",
+            sender: "sam.carter@example.org",
+            reference: "9XY87654ZW321098K",
+        },
+        Fixture {
+            id: "venmo",
+            domain: "venmo.com",
+            receiver: "@maria-lopez-7",
+            amount_text: "$32.50",
+            minor_units: 3250,
+            currency: b"USD",
+            sender: "sam.carter@example.org",
+            reference: "3812345678901234567",
+        },
+        Fixture {
+            id: "cashapp",
+            domain: "square.com",
+            receiver: "$chrisbakes",
+            amount_text: "$20.00",
+            minor_units: 2000,
+            currency: b"USD",
+            sender: "$samcarter",
+            reference: "D7K2M9P",
+        },
+    ];
+
+    #[test]
+    fn templates_extract_their_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/emails");
+        for fixture in &FIXTURES {
+            let raw_email = std::fs::read(dir.join(format!("{}.eml", fixture.id))).unwrap();
+            let signed = SignedContent::from_email(&raw_email, fixture.domain).unwrap();
+            let template = find_template(fixture.id).unwrap();
+            let payment = extract_payment(&signed, &template)
+                .unwrap_or_else(|status| panic!("{}: {:?}", fixture.id, status));
+
+            assert_eq!(payment.receiver.value, fixture.receiver, "{}", fixture.id);
+            assert_eq!(
+                payment.amount_text.value, fixture.amount_text,
+                "{}",
+                fixture.id
+            );
+            assert_eq!(
+                payment.amount.minor_units, fixture.minor_units,
+                "{}",
+                fixture.id
+            );
+            assert_eq!(&payment.amount.currency, fixture.currency, "{}", fixture.id);
+            assert_eq!(payment.sender.value, fixture.sender, "{}", fixture.id);
+            assert_eq!(payment.reference.value, fixture.reference, "{}", fixture.id);
+        }
+    }
+
+    #[test]
+    fn every_fixture_has_a_template() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/emails");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let id = path.file_stem().unwrap().to_str().unwrap();
+            assert!(FIXTURES.iter().any(|f| f.id == id), "{} is not tested", id);
+            assert!(find_template(id).is_some(), "{} has no template", id);
+        }
+    }
+
+    #[test]
+    fn every_template_has_a_sender_domain() {
+        for template in builtin_templates() {
+            assert!(!template.sender_domain.is_empty(), "{}", template.id);
+        }
+    }
+
+    #[test]
+    fn refuses_templates_without_a_sender_domain() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/emails/upi-gpay.eml");
+        let raw_email = std::fs::read(path).unwrap();
+        let signed = SignedContent::from_email(&raw_email, "google.com").unwrap();
+
+        let mut template = find_template("upi-gpay").unwrap();
+        template.sender_domain.clear();
+        assert!(!template.accepts_domain("google.com"));
+        assert_eq!(
+            extract_payment(&signed, &template),
+            Err(ExtractionStatus::InvalidTemplate)
+        );
+
+        let default = find_template(DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(
+            extract_payment(&signed, &default),
+            Err(ExtractionStatus::DomainMismatch)
+        );
+    }
+}
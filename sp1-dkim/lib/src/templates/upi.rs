@@ -0,0 +1,165 @@
+//! Templates for UPI receipts from Indian payment apps and banks.
+//!
+//! Each template reads the payee VPA, the amount, the UPI reference number and the masked debit
+//! account. Signed sample receipts for every template live in `fixtures/emails`.
+
+use crate::template::{Field, Transform};
+use crate::{FieldRule, ReceiptTemplate};
+
+/// A UPI VPA such as `name.surname@okaxis`.
+const VPA: &str = r"[\w.\-]+@[a-zA-Z]+";
+
+/// A rupee amount with its currency marker.
+const RUPEES: &str = r"(?:₹|Rs\.?|INR)\s*[\d,]+(?:\.\d{1,2})?";
+
+/// A masked account number such as `XXXX1234` or `**4321`.
+const MASKED_ACCOUNT: &str = r"[X*•]+\d{3,4}";
+
+fn upi_template(id: &str, sender_domain: &str, rules: Vec<FieldRule>) -> ReceiptTemplate {
+    ReceiptTemplate {
+        id: id.to_string(),
+        sender_domain: sender_domain.to_string(),
+        currency: (*b"INR").into(),
+        rules,
+    }
+}
+
+fn rule(field: Field, pattern: String) -> FieldRule {
+    let transform = match field {
+        Field::Receiver => Transform::Lowercase,
+        Field::Amount => Transform::CollapseWhitespace,
+        Field::Sender | Field::Reference => Transform::Identifier,
+    };
+    FieldRule::body(field, &pattern, transform)
+}
+
+/// The generic "Paid to ... ₹ ... Debited from" UPI receipt, in the layout PhonePe sends.
+pub fn paid_to() -> ReceiptTemplate {
+    ReceiptTemplate {
+        id: "upi-paid-to".to_string(),
+        sender_domain: "phonepe.com".to_string(),
+        currency: (*b"INR").into(),
+        rules: vec![
+            FieldRule::body(
+                Field::Receiver,
+                r"Paid to\s*:\s*([^\n₹]+?)\s*(?:₹|\n)",
+                Transform::CollapseWhitespace,
+            ),
+            FieldRule::body(
+                Field::Amount,
+                r"Paid to[\s\S]*?(₹\s*[\d,]+(?:\.\d+)?)",
+                Transform::None,
+            ),
+            FieldRule::body(
+                Field::Sender,
+                r"Debited from\s*:\s*([A-Z0-9]+)",
+                Transform::Identifier,
+            ),
+        ],
+    }
+}
+
+/// PhonePe "Payment Successful" emails.
+pub fn phonepe() -> ReceiptTemplate {
+    upi_template(
+        "upi-phonepe",
+        "phonepe.com",
+        vec![
+            rule(Field::Receiver, format!(r"Paid to[\s\S]*?({VPA})")),
+            rule(Field::Amount, format!(r"Paid to[\s\S]*?({RUPEES})")),
+            rule(Field::Reference, r"UTR\s*:?\s*(\d{12})".to_string()),
+            rule(
+                Field::Sender,
+                format!(r"Debited from\s*:?\s*({MASKED_ACCOUNT})"),
+            ),
+        ],
+    )
+}
+
+/// Google Pay "You paid" emails.
+pub fn gpay() -> ReceiptTemplate {
+    upi_template(
+        "upi-gpay",
+        "google.com",
+        vec![
+            rule(Field::Receiver, format!(r"UPI ID\s*:\s*({VPA})")),
+            rule(Field::Amount, format!(r"You paid\s*({RUPEES})")),
+            rule(
+                Field::Reference,
+                r"UPI transaction ID\s*:\s*(\d{12})".to_string(),
+            ),
+            rule(
+                Field::Sender,
+                format!(r"Paid from\s*:[^\n]*?({MASKED_ACCOUNT})"),
+            ),
+        ],
+    )
+}
+
+/// Paytm "Paid Successfully" emails.
+pub fn paytm() -> ReceiptTemplate {
+    upi_template(
+        "upi-paytm",
+        "paytm.com",
+        vec![
+            rule(Field::Receiver, format!(r"UPI ID\s*:\s*({VPA})")),
+            rule(Field::Amount, format!(r"Amount\s*:\s*({RUPEES})")),
+            rule(Field::Reference, r"UPI Ref No\s*:?\s*(\d{12})".to_string()),
+            rule(Field::Sender, format!(r"From A/c\s*:\s*({MASKED_ACCOUNT})")),
+        ],
+    )
+}
+
+/// HDFC Bank InstaAlerts for UPI debits.
+pub fn hdfc() -> ReceiptTemplate {
+    upi_template(
+        "upi-hdfc",
+        "hdfcbank.net",
+        vec![
+            rule(Field::Receiver, format!(r"to VPA\s+({VPA})")),
+            rule(Field::Amount, format!(r"({RUPEES})\s+has been debited")),
+            rule(
+                Field::Reference,
+                r"reference number is\s*(\d{12})".to_string(),
+            ),
+            rule(
+                Field::Sender,
+                format!(r"debited from account\s+({MASKED_ACCOUNT})"),
+            ),
+        ],
+    )
+}
+
+/// ICICI Bank account debit alerts for UPI payments.
+pub fn icici() -> ReceiptTemplate {
+    upi_template(
+        "upi-icici",
+        "icicibank.com",
+        vec![
+            rule(Field::Receiver, format!(r"credited to VPA\s+({VPA})")),
+            rule(Field::Amount, format!(r"debited with\s+({RUPEES})")),
+            rule(
+                Field::Reference,
+                r"UPI Reference No\s*:?\s*(\d{12})".to_string(),
+            ),
+            rule(Field::Sender, format!(r"Account\s+({MASKED_ACCOUNT})")),
+        ],
+    )
+}
+
+/// State Bank of India transaction alerts, which give the amount without a currency marker.
+pub fn sbi() -> ReceiptTemplate {
+    upi_template(
+        "upi-sbi",
+        "sbi.co.in",
+        vec![
+            rule(Field::Receiver, format!(r"to VPA\s+({VPA})")),
+            rule(
+                Field::Amount,
+                r"debited by\s+((?:Rs\.?|INR)?\s*[\d,]+(?:\.\d{1,2})?)".to_string(),
+            ),
+            rule(Field::Reference, r"UPI Ref No\.?\s*(\d{12})".to_string()),
+            rule(Field::Sender, format!(r"A/C\s+({MASKED_ACCOUNT})")),
+        ],
+    )
+}
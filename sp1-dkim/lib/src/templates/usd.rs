@@ -0,0 +1,147 @@
+//! Templates for US dollar receipts from PayPal, Venmo and Cash App.
+//!
+//! Each template reads the payee, the amount, the transaction ID and the payer. PayPal and Venmo
+//! send the receipt to the payer, so the payer is read from the signed `To` header; Cash App names
+//! the payer's cashtag in the body. Signed sample receipts live in `fixtures/emails`.
+
+use crate::template::{Field, Transform};
+use crate::{FieldRule, ReceiptTemplate};
+
+/// An email address.
+const EMAIL: &str = r"[\w.+\-]+@[\w\-]+(?:\.[\w\-]+)+";
+
+/// A dollar amount such as `$1,234.50`.
+const DOLLARS: &str = r"\$[\d,]+(?:\.\d{1,2})?";
+
+/// A Cash App `$cashtag`.
+const CASHTAG: &str = r"\$[A-Za-z][\w\-]*";
+
+fn usd_template(id: &str, sender_domain: &str, rules: Vec<FieldRule>) -> ReceiptTemplate {
+    ReceiptTemplate {
+        id: id.to_string(),
+        sender_domain: sender_domain.to_string(),
+        currency: (*b"USD").into(),
+        rules,
+    }
+}
+
+/// The payer, as the recipient of the receipt.
+fn payer_from_to_header() -> FieldRule {
+    FieldRule::header(
+        Field::Sender,
+        "To",
+        &format!("({EMAIL})"),
+        Transform::Lowercase,
+    )
+}
+
+/// PayPal "You sent $X USD to ..." receipts.
+pub fn paypal() -> ReceiptTemplate {
+    usd_template(
+        "paypal",
+        "paypal.com",
+        vec![
+            FieldRule::body(
+                Field::Receiver,
+                &format!(r"Sent to[\s\S]*?({EMAIL})"),
+                Transform::Lowercase,
+            ),
+            FieldRule::body(
+                Field::Amount,
+                &format!(r"You sent\s+({DOLLARS})\s*USD"),
+                Transform::None,
+            ),
+            FieldRule::body(
+                Field::Reference,
+                r"Transaction ID\s*:?\s*([A-Z0-9]{17})",
+                Transform::Identifier,
+            ),
+            payer_from_to_header(),
+        ],
+    )
+}
+
+/// PayPal USD (PYUSD) transfer confirmations.
+///
+/// This is a separate template from [`paypal`] so that its hash tells a PYUSD transfer apart from
+/// a dollar payment, as both are committed in USD.
+pub fn paypal_pyusd() -> ReceiptTemplate {
+    usd_template(
+        "paypal-pyusd",
+        "paypal.com",
+        vec![
+            FieldRule::body(
+                Field::Receiver,
+                &format!(r"PYUSD to\s+({EMAIL})"),
+                Transform::Lowercase,
+            ),
+            FieldRule::body(
+                Field::Amount,
+                r"You sent\s+([\d,]+(?:\.\d{1,2})?\s*PYUSD)",
+                Transform::CollapseWhitespace,
+            ),
+            FieldRule::body(
+                Field::Reference,
+                r"Transaction ID\s*:?\s*([A-Z0-9]{17})",
+                Transform::Identifier,
+            ),
+            payer_from_to_header(),
+        ],
+    )
+}
+
+/// Venmo "You paid ..." receipts, naming the payee by `@username`.
+pub fn venmo() -> ReceiptTemplate {
+    usd_template(
+        "venmo",
+        "venmo.com",
+        vec![
+            FieldRule::body(
+                Field::Receiver,
+                r"You paid[^@$]*(@[\w\-]+)",
+                Transform::Lowercase,
+            ),
+            FieldRule::body(
+                Field::Amount,
+                &format!(r"You paid[^$]*?({DOLLARS})"),
+                Transform::None,
+            ),
+            FieldRule::body(
+                Field::Reference,
+                r"Transaction ID\s*:?\s*(\d{10,20})",
+                Transform::Identifier,
+            ),
+            payer_from_to_header(),
+        ],
+    )
+}
+
+/// Cash App "You sent $X to ..." receipts, which come from Square.
+pub fn cashapp() -> ReceiptTemplate {
+    usd_template(
+        "cashapp",
+        "square.com",
+        vec![
+            FieldRule::body(
+                Field::Receiver,
+                &format!(r"You sent\s+{DOLLARS}\s+to[^\n]*\n\s*({CASHTAG})"),
+                Transform::Lowercase,
+            ),
+            FieldRule::body(
+                Field::Amount,
+                &format!(r"You sent\s+({DOLLARS})"),
+                Transform::None,
+            ),
+            FieldRule::body(
+                Field::Reference,
+                r"Identifier\s*:?\s*#?([A-Z0-9]{6,})",
+                Transform::Identifier,
+            ),
+            FieldRule::body(
+                Field::Sender,
+                &format!(r"Payment from\s+({CASHTAG})"),
+                Transform::Lowercase,
+            ),
+        ],
+    )
+}
@@ -0,0 +1,160 @@
+//! Timestamps of a signed email and the window a proof is bound to.
+//!
+//! Contracts enforce deadlines against these, so they are only read from signed content: the
+//! `Date` header if it is listed in `h=`, and the signature's own `t=` and `x=` tags.
+
+use crate::dkim::SignedContent;
+
+/// The times recorded in a signed email, in unix seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timestamps {
+    /// The signed `Date` header.
+    pub date: Option<u64>,
+    /// The DKIM `t=` tag.
+    pub signed_at: Option<u64>,
+    /// The DKIM `x=` tag.
+    pub expires_at: Option<u64>,
+}
+
+impl Timestamps {
+    /// Reads the times of a signature that verified. The `t=` and `x=` of any other signature
+    /// are whatever its author chose.
+    pub fn from_signed(signed: &SignedContent) -> Self {
+        let date = signed
+            .header("Date")
+            .and_then(|date| mailparse::dateparse(date).ok())
+            .and_then(|date| u64::try_from(date).ok());
+        Self {
+            date,
+            signed_at: signed.signature.timestamp,
+            expires_at: signed.signature.expiration,
+        }
+    }
+
+    /// Whether `x=` is not after `t=`, which RFC 6376 forbids. Such a signature was already
+    /// expired when it was made.
+    pub fn expired_at_signing(&self) -> bool {
+        match (self.signed_at, self.expires_at) {
+            (Some(signed_at), Some(expires_at)) => expires_at <= signed_at,
+            _ => false,
+        }
+    }
+
+    /// The time the email was sent: `t=` if the signature has one, otherwise the `Date` header.
+    pub fn sent_at(&self) -> Option<u64> {
+        self.signed_at.or(self.date)
+    }
+}
+
+/// The interval the email must have been sent in, in unix seconds. A bound of zero is open.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub not_before: u64,
+    pub not_after: u64,
+}
+
+impl TimeWindow {
+    pub fn new(not_before: Option<u64>, not_after: Option<u64>) -> Self {
+        Self {
+            not_before: not_before.unwrap_or(0),
+            not_after: not_after.unwrap_or(0),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.not_before == 0 && self.not_after == 0
+    }
+
+    /// Whether `time` lies in the window. An unknown time only lies in an open window.
+    pub fn contains(&self, time: Option<u64>) -> bool {
+        match time {
+            None => self.is_open(),
+            Some(time) => {
+                time >= self.not_before && (self.not_after == 0 || time <= self.not_after)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dkim::{DkimSignature, Header};
+
+    fn signed(tags: &str, date: Option<&str>) -> SignedContent {
+        let value = format!("a=rsa-sha256; d=example.com; h=from:date; {}; b=AQID", tags);
+        SignedContent {
+            signature: DkimSignature::parse(&value).unwrap(),
+            headers: date
+                .iter()
+                .map(|date| Header {
+                    name: "Date".to_string(),
+                    value: date.to_string(),
+                })
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reads_signed_times() {
+        let timestamps = Timestamps::from_signed(&signed(
+            "t=1700000000; x=1700086400",
+            Some("Tue, 14 Nov 2023 22:13:20 +0000"),
+        ));
+        assert_eq!(
+            timestamps,
+            Timestamps {
+                date: Some(1_700_000_000),
+                signed_at: Some(1_700_000_000),
+                expires_at: Some(1_700_086_400),
+            }
+        );
+        assert!(!timestamps.expired_at_signing());
+    }
+
+    #[test]
+    fn sends_at_t_before_date() {
+        let both =
+            Timestamps::from_signed(&signed("t=100", Some("Thu, 1 Jan 1970 00:03:20 +0000")));
+        assert_eq!(both.sent_at(), Some(100));
+        let date_only =
+            Timestamps::from_signed(&signed("v=1", Some("Thu, 1 Jan 1970 00:03:20 +0000")));
+        assert_eq!(date_only.sent_at(), Some(200));
+        let neither = Timestamps::from_signed(&signed("v=1", None));
+        assert_eq!(neither.sent_at(), None);
+    }
+
+    #[test]
+    fn expires_at_signing_unless_x_is_after_t() {
+        let expired = |tags| Timestamps::from_signed(&signed(tags, None)).expired_at_signing();
+        assert!(expired("t=100; x=100"));
+        assert!(expired("t=100; x=99"));
+        assert!(!expired("t=100; x=101"));
+        assert!(!expired("x=1"));
+        assert!(!expired("t=100"));
+    }
+
+    #[test]
+    fn window_bounds_are_inclusive_and_zero_is_open() {
+        let open = TimeWindow::new(None, None);
+        assert!(open.is_open());
+        assert!(open.contains(None));
+        assert!(open.contains(Some(0)));
+
+        let window = TimeWindow::new(Some(100), Some(200));
+        assert!(!window.is_open());
+        assert!(!window.contains(None));
+        assert!(!window.contains(Some(99)));
+        assert!(window.contains(Some(100)));
+        assert!(window.contains(Some(200)));
+        assert!(!window.contains(Some(201)));
+
+        let not_before = TimeWindow::new(Some(100), None);
+        assert!(not_before.contains(Some(u64::MAX)));
+        assert!(!not_before.contains(Some(99)));
+        let not_after = TimeWindow::new(None, Some(200));
+        assert!(not_after.contains(Some(0)));
+        assert!(!not_after.contains(Some(201)));
+    }
+}
@@ -1,57 +1,391 @@
 #![no_main]
 
-use cfdkim::{verify_email_with_public_key, DkimPublicKey};
+use cfdkim::{verify_email_with_public_key, DKIMError, DkimPublicKey};
 use mailparse::parse_mail;
 use sha2::{Digest, Sha256};
-use sp1_zkvm::io::{commit, commit_slice, read, read_vec};
+use sp1_zkvm::io::{commit_slice, read, read_vec};
+use alloy_primitives::U256;
 use alloy_sol_types::SolType;
-use fibonacci_lib::PublicValuesStruct;
-use regex::Regex;
+use fibonacci_lib::account::{signed_account_hash, ACCOUNT_SALT_LEN};
+use fibonacci_lib::alignment::{from_alignment, Alignment};
+use fibonacci_lib::command::{addressed_to, signed_command};
+use fibonacci_lib::dkim::{verify_signed_content, SignedContent};
+use fibonacci_lib::privacy::{field_commitment, FieldSalts};
+use fibonacci_lib::registration::signed_registration;
+use fibonacci_lib::{
+    extract_payment, CommandValuesStruct, EmailMode, ErrorCode, ExtractionStatus,
+    PublicValuesStruct, ReceiptTemplate, Recipient, RegistrationValuesStruct, TimeWindow,
+    Timestamps,
+};
 
 sp1_zkvm::entrypoint!(main);
 
+/// The values every mode commits, filled in as the checks pass.
+#[derive(Default)]
+struct Verified {
+    nullifier: [u8; 32],
+    from_alignment: u8,
+    timestamps: Timestamps,
+    result: bool,
+}
+
 pub fn main() {
+    let raw_mode = read::<u8>();
+    let Ok(mode) = EmailMode::try_from(raw_mode) else {
+        // Nothing says how the rest of an unknown mode's input is laid out, so nothing more is
+        // read and the error is committed in the receipt layout.
+        let public_values = PublicValuesStruct {
+            mode: raw_mode,
+            error_code: ErrorCode::UnknownMode as u8,
+            ..Default::default()
+        };
+        commit_slice(&PublicValuesStruct::abi_encode(&public_values));
+        return;
+    };
     let from_domain = read::<String>();
     let raw_email = read_vec();
     let public_key_type = read::<String>();
     let public_key_vec = read_vec();
-
-    let email = parse_mail(&raw_email).unwrap();
-    let public_key = DkimPublicKey::from_vec_with_type(&public_key_vec, &public_key_type);
+    // The template is only read for receipts, and the field salts, which stay private, only for
+    // private receipts. Emails a user sends to the relayer come with the relayer address and the
+    // account salt, which also stays private, and registration replies with the challenge nonce.
+    let template_vec = match mode {
+        EmailMode::Receipt | EmailMode::PrivateReceipt => read_vec(),
+        EmailMode::Command | EmailMode::Registration => Vec::new(),
+    };
+    let field_salts = match mode {
+        EmailMode::PrivateReceipt => Some(FieldSalts {
+            receiver: read::<[u8; 32]>(),
+            sender: read::<[u8; 32]>(),
+        }),
+        EmailMode::Receipt | EmailMode::Command | EmailMode::Registration => None,
+    };
+    let (relayer, account_salt) = match mode {
+        EmailMode::Receipt | EmailMode::PrivateReceipt => (String::new(), [0u8; ACCOUNT_SALT_LEN]),
+        EmailMode::Command | EmailMode::Registration => (
+            read::<String>().trim().to_ascii_lowercase(),
+            read::<[u8; ACCOUNT_SALT_LEN]>(),
+        ),
+    };
+    let nonce = match mode {
+        EmailMode::Registration => read::<[u8; 32]>(),
+        EmailMode::Receipt | EmailMode::PrivateReceipt | EmailMode::Command => [0u8; 32],
+    };
+    let not_before = read::<u64>();
+    let not_after = read::<u64>();
+    let claimant = read::<[u8; 20]>();
+    let chain_id = read::<u64>();
+    let contract_address = read::<[u8; 20]>();
 
     let mut hasher = Sha256::new();
-    hasher.update(public_key_vec);
-    let public_key_hash = hasher.finalize();
+    hasher.update(&public_key_vec);
+    let public_key_hash: [u8; 32] = hasher.finalize().into();
 
     let mut hasher = Sha256::new();
     hasher.update(from_domain.as_bytes());
-    let from_domain_hash = hasher.finalize();
+    let from_domain_hash: [u8; 32] = hasher.finalize().into();
+
+    // The relayer is committed whether or not the email was sent to it, so a contract can check
+    // the proof was made for its own relayer.
+    let mut hasher = Sha256::new();
+    hasher.update(relayer.as_bytes());
+    let relayer_hash: [u8; 32] = hasher.finalize().into();
+
+    // Every failure is committed as an error code rather than aborting, so the host can tell
+    // which check an email failed. Values a failed check would have produced are left empty.
+    let window = TimeWindow { not_before, not_after };
+    let mut verified = Verified::default();
+    let signed = verify_email(
+        &from_domain,
+        &raw_email,
+        &public_key_type,
+        &public_key_vec,
+        window,
+        &mut verified,
+    );
+
+    // Commit the public values. The layout of each mode is the same whether or not the email
+    // matched.
+    match mode {
+        EmailMode::Receipt | EmailMode::PrivateReceipt => {
+            let mut public_values = PublicValuesStruct {
+                mode: mode as u8,
+                from_domain_hash: from_domain_hash.into(),
+                public_key_hash: public_key_hash.into(),
+                extraction_status: ExtractionStatus::NotFound as u8,
+                ..Default::default()
+            };
+            let error_code = match extract_receipt(
+                signed,
+                &template_vec,
+                field_salts.as_ref(),
+                &mut public_values,
+            ) {
+                Ok(()) => ErrorCode::NoError,
+                Err(code) => code,
+            };
+
+            public_values.nullifier = verified.nullifier.into();
+            public_values.result = verified.result;
+            public_values.error_code = error_code as u8;
+            public_values.from_alignment = verified.from_alignment;
+            public_values.date = verified.timestamps.date.unwrap_or(0);
+            public_values.dkim_timestamp = verified.timestamps.signed_at.unwrap_or(0);
+            public_values.dkim_expiration = verified.timestamps.expires_at.unwrap_or(0);
+            public_values.not_before = not_before;
+            public_values.not_after = not_after;
+            // The proof only pays out to the claimant on this chain and contract, so a copy of it
+            // taken from the mempool is useless to anyone else.
+            public_values.claimant = claimant.into();
+            public_values.chain_id = U256::from(chain_id);
+            public_values.contract_address = contract_address.into();
+            commit_slice(&PublicValuesStruct::abi_encode(&public_values));
+        }
+        EmailMode::Command => {
+            let mut command_values = CommandValuesStruct {
+                mode: mode as u8,
+                from_domain_hash: from_domain_hash.into(),
+                public_key_hash: public_key_hash.into(),
+                relayer_hash: relayer_hash.into(),
+                ..Default::default()
+            };
+            let error_code = match signed
+                .and_then(|signed| {
+                    parse_command(&signed, &relayer, &account_salt, &mut command_values)
+                })
+            {
+                Ok(()) => ErrorCode::NoError,
+                Err(code) => code,
+            };
+
+            command_values.nullifier = verified.nullifier.into();
+            command_values.result = verified.result;
+            command_values.error_code = error_code as u8;
+            command_values.from_alignment = verified.from_alignment;
+            command_values.date = verified.timestamps.date.unwrap_or(0);
+            command_values.dkim_timestamp = verified.timestamps.signed_at.unwrap_or(0);
+            command_values.dkim_expiration = verified.timestamps.expires_at.unwrap_or(0);
+            command_values.not_before = not_before;
+            command_values.not_after = not_after;
+            command_values.claimant = claimant.into();
+            command_values.chain_id = U256::from(chain_id);
+            command_values.contract_address = contract_address.into();
+            commit_slice(&CommandValuesStruct::abi_encode(&command_values));
+        }
+        EmailMode::Registration => {
+            // The nonce is the one the registry issued, so it is committed even if the reply
+            // does not match it.
+            let mut registration_values = RegistrationValuesStruct {
+                mode: mode as u8,
+                from_domain_hash: from_domain_hash.into(),
+                public_key_hash: public_key_hash.into(),
+                relayer_hash: relayer_hash.into(),
+                nonce: nonce.into(),
+                ..Default::default()
+            };
+            let error_code = match signed.and_then(|signed| {
+                parse_registration(
+                    &signed,
+                    &relayer,
+                    &account_salt,
+                    &nonce,
+                    &mut registration_values,
+                )
+            }) {
+                Ok(()) => ErrorCode::NoError,
+                Err(code) => code,
+            };
+
+            registration_values.nullifier = verified.nullifier.into();
+            registration_values.result = verified.result;
+            registration_values.error_code = error_code as u8;
+            registration_values.from_alignment = verified.from_alignment;
+            registration_values.date = verified.timestamps.date.unwrap_or(0);
+            registration_values.dkim_timestamp = verified.timestamps.signed_at.unwrap_or(0);
+            registration_values.dkim_expiration = verified.timestamps.expires_at.unwrap_or(0);
+            registration_values.not_before = not_before;
+            registration_values.not_after = not_after;
+            registration_values.claimant = claimant.into();
+            registration_values.chain_id = U256::from(chain_id);
+            registration_values.contract_address = contract_address.into();
+            commit_slice(&RegistrationValuesStruct::abi_encode(&registration_values));
+        }
+    }
+}
+
+/// Verifies the email, stopping at the first check that fails, and returns what its signature
+/// covers.
+fn verify_email(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key_type: &str,
+    public_key_vec: &[u8],
+    window: TimeWindow,
+    verified: &mut Verified,
+) -> Result<SignedContent, ErrorCode> {
+    parse_mail(raw_email).map_err(|_| ErrorCode::ParseError)?;
+
+    // cfdkim passes if any signature from the domain verifies, so each one is verified alone and
+    // everything is read from the first that passes. The times of a signature that verified are
+    // committed even when they are refused.
+    let (signed, timestamps) =
+        verify_signed_content(raw_email, from_domain, window, |signed, isolated| {
+            verify_signature(from_domain, signed, isolated, public_key_type, public_key_vec)
+        })
+        .map_err(|err| {
+            verified.timestamps = err.timestamps().unwrap_or_default();
+            err.error_code()
+        })?;
+    verified.timestamps = timestamps;
+
+    // The nullifier identifies the email by the signature that verified, so each email can be
+    // redeemed once however many forged signatures are added beside it.
+    verified.nullifier = signed.signature.nullifier();
+    // The signed From address must belong to the signing domain for the sender to mean anything.
+    verified.from_alignment = from_alignment(&signed) as u8;
+    verified.result = true;
+    Ok(signed)
+}
 
-    commit_slice(&from_domain_hash);
-    commit_slice(&public_key_hash);
+/// Verifies one signature, given the email with every other signature from its domain removed.
+fn verify_signature(
+    from_domain: &str,
+    signed: &SignedContent,
+    isolated: &[u8],
+    public_key_type: &str,
+    public_key_vec: &[u8],
+) -> Result<(), ErrorCode> {
+    // Check the key type before decoding the key, which would fail on a key of the wrong type.
+    if !signed.signature.key_type().eq_ignore_ascii_case(public_key_type) {
+        return Err(ErrorCode::KeyTypeMismatch);
+    }
+    let email = parse_mail(isolated).map_err(|_| ErrorCode::ParseError)?;
+    let public_key = DkimPublicKey::from_vec_with_type(public_key_vec, public_key_type);
+    let result = verify_email_with_public_key(from_domain, &email, &public_key)
+        .map_err(|err| dkim_error_code(&err))?;
+    if result.summary() != "pass" {
+        return Err(result
+            .error()
+            .map_or(ErrorCode::DkimFailure, |err| dkim_error_code(&err)));
+    }
+    Ok(())
+}
+
+/// Extracts the payment details from the signed content only.
+///
+/// The template hash is committed even if the email failed verification. With field salts, the
+/// receiver and sender are committed as salted hashes and their values are left empty.
+fn extract_receipt(
+    signed: Result<SignedContent, ErrorCode>,
+    template_vec: &[u8],
+    field_salts: Option<&FieldSalts>,
+    public_values: &mut PublicValuesStruct,
+) -> Result<(), ErrorCode> {
+    let template =
+        ReceiptTemplate::abi_decode(template_vec, true).map_err(|_| ErrorCode::InvalidTemplate)?;
+    public_values.template_hash = template.hash().into();
+
+    let signed = signed?;
+    let payment = extract_payment(&signed, &template).map_err(|status| {
+        public_values.extraction_status = status as u8;
+        ErrorCode::ExtractionMiss
+    })?;
+    public_values.extraction_status = ExtractionStatus::Extracted as u8;
+    let (mut receiver, mut sender) = (payment.receiver, payment.sender);
+    if let Some(salts) = field_salts {
+        // The region stays public; only the value could identify an account.
+        receiver.hash = field_commitment(&receiver.value, &salts.receiver).into();
+        sender.hash = field_commitment(&sender.value, &salts.sender).into();
+        receiver.value.clear();
+        sender.value.clear();
+    }
+    public_values.receiver = receiver;
+    public_values.amount_text = payment.amount_text;
+    public_values.sender = sender;
+    public_values.reference = payment.reference;
+    public_values.amount = U256::from(payment.amount.minor_units);
+    public_values.currency = payment.amount.currency.into();
+    Ok(())
+}
+
+/// Parses the command in the signed subject, authorised by the signed From address and sent to
+/// the relayer.
+fn parse_command(
+    signed: &SignedContent,
+    relayer: &str,
+    account_salt: &[u8; ACCOUNT_SALT_LEN],
+    command_values: &mut CommandValuesStruct,
+) -> Result<(), ErrorCode> {
+    // Anyone can send from a large provider, so the From address only names the sender when its
+    // domain is the signing domain itself. Relaxed alignment would let a subdomain's key sign
+    // for the parent's accounts.
+    if from_alignment(signed) != Alignment::Strict {
+        return Err(ErrorCode::UnalignedFrom);
+    }
+
+    // Contracts match the sender's account by the salted hash of their signed From address, which
+    // stays off-chain.
+    let account_hash = signed_account_hash(signed, account_salt).ok_or(ErrorCode::CommandMiss)?;
+    command_values.account_hash = account_hash.into();
 
+    // A command sent anywhere else could be replayed to the relayer as an instruction.
+    if !addressed_to(signed, relayer) {
+        return Err(ErrorCode::NotAddressedToRelayer);
+    }
 
-    let result = verify_email_with_public_key(&from_domain, &email, &public_key).unwrap();
-    let is_verified = result.summary() == "pass";
+    let command = signed_command(signed).ok_or(ErrorCode::CommandMiss)?;
+    command_values.command = command.kind as u8;
+    command_values.amount = U256::from(command.mantissa);
+    command_values.decimals = command.decimals;
+    command_values.token = command.token;
+    command_values.recipient_kind = command.recipient.kind() as u8;
+    command_values.recipient = command.recipient.text();
+    if let Recipient::Address(address) = command.recipient {
+        command_values.recipient_address = address.into();
+    }
+    Ok(())
+}
 
-    if is_verified{
-        commit(&true);
+/// Parses the wallet in a signed reply to the registration challenge with the given nonce, sent
+/// to the relayer by the owner of the From address.
+fn parse_registration(
+    signed: &SignedContent,
+    relayer: &str,
+    account_salt: &[u8; ACCOUNT_SALT_LEN],
+    nonce: &[u8; 32],
+    registration_values: &mut RegistrationValuesStruct,
+) -> Result<(), ErrorCode> {
+    if from_alignment(signed) != Alignment::Strict {
+        return Err(ErrorCode::UnalignedFrom);
     }
-    else{
-        commit(&false);
+    let account_hash =
+        signed_account_hash(signed, account_salt).ok_or(ErrorCode::RegistrationMiss)?;
+    registration_values.account_hash = account_hash.into();
+
+    // A reply sent anywhere else is not an answer to our challenge.
+    if !addressed_to(signed, relayer) {
+        return Err(ErrorCode::NotAddressedToRelayer);
     }
-    
-    // Extract information using regex
-    let email_body = String::from_utf8_lossy(&raw_email);
-    // Updated regex pattern to remove look-ahead
-    let re = Regex::new(r"Paid to\s*:\s*(.+?)\s*.*?₹\s*(\d+(?:\.\d{2})?).*?Debited from\s*:\s*([A-Z0-9]+)").unwrap();
 
-    if let Some(captures) = re.captures(&email_body) {
-        commit(&captures.get(1).map_or("", |m| m.as_str()).to_string());
-        commit(&captures.get(2).map_or("", |m| m.as_str()).to_string());
-        commit(&captures.get(3).map_or("", |m| m.as_str()).to_string());
+    let registration = signed_registration(signed).ok_or(ErrorCode::RegistrationMiss)?;
+    // An old reply must not register the wallet again for a new challenge.
+    if registration.nonce != *nonce {
+        return Err(ErrorCode::NonceMismatch);
     }
+    registration_values.wallet = registration.wallet.into();
+    Ok(())
+}
 
-    // Commit the public values
-    
+fn dkim_error_code(err: &DKIMError) -> ErrorCode {
+    match err {
+        DKIMError::BodyHashDidNotVerify => ErrorCode::BodyHashMismatch,
+        DKIMError::SignatureDidNotVerify => ErrorCode::SignatureInvalid,
+        DKIMError::InappropriateKeyAlgorithm => ErrorCode::KeyTypeMismatch,
+        DKIMError::SignatureExpired => ErrorCode::SignatureExpired,
+        DKIMError::SignatureSyntaxError(_)
+        | DKIMError::SignatureMissingRequiredTag(_)
+        | DKIMError::KeySyntaxError
+        | DKIMError::MalformedBody => ErrorCode::ParseError,
+        _ => ErrorCode::DkimFailure,
+    }
 }
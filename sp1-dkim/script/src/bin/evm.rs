@@ -0,0 +1,312 @@
+//! An end-to-end example of using the SP1 SDK to generate a proof of the DKIM program that can be
+//! verified on-chain, and to write a fixture for the Solidity contract tests.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin evm -- --domain <domain> --email <path> --system groth16
+//! ```
+//! or
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin evm -- --domain <domain> --email <path> --system plonk
+//! ```
+//! Pass `--command` to prove a command email sent to `--relayer` instead of a receipt; its fixture
+//! is written to `<system>-command-fixture.json`. A registration reply proven with
+//! `--registration` is written to `<system>-registration-fixture.json`. With `--private`, the
+//! openings of the hashed receipt fields are saved to `--openings`. Every mode takes the
+//! `--claimant`, `--chain-id` and `--contract-address` the proof is bound to.
+
+use alloy_sol_types::SolType;
+use clap::{Parser, ValueEnum};
+use fibonacci_lib::{
+    committed_mode, CommandValuesStruct, EmailMode, PublicValuesStruct, RegistrationValuesStruct,
+};
+use fibonacci_script::openings::save_openings;
+use fibonacci_script::{InputArgs, ELF};
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{HashableKey, ProverClient, SP1ProofWithPublicValues, SP1VerifyingKey};
+use std::path::{Path, PathBuf};
+
+/// The arguments for the EVM command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct EVMArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(long, value_enum, default_value = "groth16")]
+    system: ProofSystem,
+    /// Prove even if the native pre-flight check fails.
+    #[clap(long)]
+    force: bool,
+    /// Directory the fixture is written to.
+    #[clap(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../contracts/src/fixtures"))]
+    fixture_dir: PathBuf,
+}
+
+/// Enum representing the available proof systems
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ProofSystem {
+    Plonk,
+    Groth16,
+}
+
+/// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SP1DkimProofFixture {
+    from_domain_hash: String,
+    public_key_hash: String,
+    template_hash: String,
+    nullifier: String,
+    result: bool,
+    error_code: u8,
+    from_alignment: u8,
+    extraction_status: u8,
+    receiver: String,
+    sender: String,
+    reference: String,
+    receiver_hash: String,
+    sender_hash: String,
+    amount: String,
+    currency: String,
+    date: u64,
+    dkim_timestamp: u64,
+    dkim_expiration: u64,
+    not_before: u64,
+    not_after: u64,
+    claimant: String,
+    chain_id: String,
+    contract_address: String,
+    vkey: String,
+    public_values: String,
+    proof: String,
+}
+
+/// A fixture for a command email proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SP1DkimCommandFixture {
+    from_domain_hash: String,
+    public_key_hash: String,
+    nullifier: String,
+    result: bool,
+    error_code: u8,
+    from_alignment: u8,
+    account_hash: String,
+    relayer_hash: String,
+    command: u8,
+    amount: String,
+    decimals: u8,
+    token: String,
+    recipient_kind: u8,
+    recipient: String,
+    recipient_address: String,
+    date: u64,
+    dkim_timestamp: u64,
+    dkim_expiration: u64,
+    not_before: u64,
+    not_after: u64,
+    claimant: String,
+    chain_id: String,
+    contract_address: String,
+    vkey: String,
+    public_values: String,
+    proof: String,
+}
+
+/// A fixture for a registration reply proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SP1DkimRegistrationFixture {
+    from_domain_hash: String,
+    public_key_hash: String,
+    nullifier: String,
+    result: bool,
+    error_code: u8,
+    from_alignment: u8,
+    account_hash: String,
+    relayer_hash: String,
+    wallet: String,
+    nonce: String,
+    date: u64,
+    dkim_timestamp: u64,
+    dkim_expiration: u64,
+    not_before: u64,
+    not_after: u64,
+    claimant: String,
+    chain_id: String,
+    contract_address: String,
+    vkey: String,
+    public_values: String,
+    proof: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse the command line arguments.
+    let args = EVMArgs::parse();
+
+    let (stdin, openings) = args.input.load(Some(args.force)).await?;
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+
+    // Setup the program.
+    let (pk, vk) = client.setup(ELF);
+
+    println!("domain: {}", args.input.email.domain);
+    println!("Proof System: {:?}", args.system);
+
+    // Generate the proof based on the selected proof system.
+    let proof = match args.system {
+        ProofSystem::Plonk => client.prove(&pk, stdin).plonk().run(),
+        ProofSystem::Groth16 => client.prove(&pk, stdin).groth16().run(),
+    }?;
+
+    if let Some(openings) = openings {
+        save_openings(
+            &args.input.mode.openings,
+            &openings,
+            proof.public_values.as_slice(),
+        )?;
+    }
+    create_proof_fixture(&proof, &vk, args.system, &args.fixture_dir)
+}
+
+/// Create a fixture for the given proof.
+fn create_proof_fixture(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    system: ProofSystem,
+    fixture_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Deserialize the public values.
+    let bytes = proof.public_values.as_slice();
+    let vkey = vk.bytes32().to_string();
+    let public_values_hex = format!("0x{}", hex::encode(bytes));
+    let proof_hex = format!("0x{}", hex::encode(proof.bytes()));
+
+    // The verification key is used to verify that the proof corresponds to the execution of the
+    // program on the given input.
+    //
+    // Note that the verification key stays the same regardless of the input.
+    println!("Verification Key: {}", vkey);
+
+    // The public values are the values which are publicly committed to by the zkVM.
+    //
+    // If you need to expose the inputs or outputs of your program, you should commit them in
+    // the public values.
+    println!("Public Values: {}", public_values_hex);
+
+    // The proof proves to the verifier that the program was executed with some inputs that led to
+    // the give public values.
+    println!("Proof Bytes: {}", proof_hex);
+
+    // Create the testing fixture so we can test things end-to-end.
+    let (name, fixture) = match committed_mode(bytes) {
+        Some(EmailMode::Receipt | EmailMode::PrivateReceipt) => {
+            let public_values = PublicValuesStruct::abi_decode(bytes, true)?;
+            let fixture = SP1DkimProofFixture {
+                from_domain_hash: public_values.from_domain_hash.to_string(),
+                public_key_hash: public_values.public_key_hash.to_string(),
+                template_hash: public_values.template_hash.to_string(),
+                nullifier: public_values.nullifier.to_string(),
+                result: public_values.result,
+                error_code: public_values.error_code,
+                from_alignment: public_values.from_alignment,
+                extraction_status: public_values.extraction_status,
+                receiver: public_values.receiver.value,
+                sender: public_values.sender.value,
+                reference: public_values.reference.value,
+                receiver_hash: public_values.receiver.hash.to_string(),
+                sender_hash: public_values.sender.hash.to_string(),
+                amount: public_values.amount.to_string(),
+                currency: String::from_utf8_lossy(public_values.currency.as_slice()).into_owned(),
+                date: public_values.date,
+                dkim_timestamp: public_values.dkim_timestamp,
+                dkim_expiration: public_values.dkim_expiration,
+                not_before: public_values.not_before,
+                not_after: public_values.not_after,
+                claimant: public_values.claimant.to_string(),
+                chain_id: public_values.chain_id.to_string(),
+                contract_address: public_values.contract_address.to_string(),
+                vkey,
+                public_values: public_values_hex,
+                proof: proof_hex,
+            };
+            ("fixture", serde_json::to_string_pretty(&fixture)?)
+        }
+        Some(EmailMode::Command) => {
+            let command_values = CommandValuesStruct::abi_decode(bytes, true)?;
+            let fixture = SP1DkimCommandFixture {
+                from_domain_hash: command_values.from_domain_hash.to_string(),
+                public_key_hash: command_values.public_key_hash.to_string(),
+                nullifier: command_values.nullifier.to_string(),
+                result: command_values.result,
+                error_code: command_values.error_code,
+                from_alignment: command_values.from_alignment,
+                account_hash: command_values.account_hash.to_string(),
+                relayer_hash: command_values.relayer_hash.to_string(),
+                command: command_values.command,
+                amount: command_values.amount.to_string(),
+                decimals: command_values.decimals,
+                token: command_values.token,
+                recipient_kind: command_values.recipient_kind,
+                recipient: command_values.recipient,
+                recipient_address: command_values.recipient_address.to_string(),
+                date: command_values.date,
+                dkim_timestamp: command_values.dkim_timestamp,
+                dkim_expiration: command_values.dkim_expiration,
+                not_before: command_values.not_before,
+                not_after: command_values.not_after,
+                claimant: command_values.claimant.to_string(),
+                chain_id: command_values.chain_id.to_string(),
+                contract_address: command_values.contract_address.to_string(),
+                vkey,
+                public_values: public_values_hex,
+                proof: proof_hex,
+            };
+            ("command-fixture", serde_json::to_string_pretty(&fixture)?)
+        }
+        Some(EmailMode::Registration) => {
+            let registration_values = RegistrationValuesStruct::abi_decode(bytes, true)?;
+            let fixture = SP1DkimRegistrationFixture {
+                from_domain_hash: registration_values.from_domain_hash.to_string(),
+                public_key_hash: registration_values.public_key_hash.to_string(),
+                nullifier: registration_values.nullifier.to_string(),
+                result: registration_values.result,
+                error_code: registration_values.error_code,
+                from_alignment: registration_values.from_alignment,
+                account_hash: registration_values.account_hash.to_string(),
+                relayer_hash: registration_values.relayer_hash.to_string(),
+                wallet: registration_values.wallet.to_string(),
+                nonce: registration_values.nonce.to_string(),
+                date: registration_values.date,
+                dkim_timestamp: registration_values.dkim_timestamp,
+                dkim_expiration: registration_values.dkim_expiration,
+                not_before: registration_values.not_before,
+                not_after: registration_values.not_after,
+                claimant: registration_values.claimant.to_string(),
+                chain_id: registration_values.chain_id.to_string(),
+                contract_address: registration_values.contract_address.to_string(),
+                vkey,
+                public_values: public_values_hex,
+                proof: proof_hex,
+            };
+            (
+                "registration-fixture",
+                serde_json::to_string_pretty(&fixture)?,
+            )
+        }
+        None => return Err("the public values do not start with a known mode".into()),
+    };
+
+    // Save the fixture to a file.
+    std::fs::create_dir_all(fixture_dir)?;
+    let fixture_path = fixture_dir.join(format!("{:?}-{}.json", system, name).to_lowercase());
+    std::fs::write(&fixture_path, fixture)?;
+    println!("Fixture written to {}", fixture_path.display());
+    Ok(())
+}
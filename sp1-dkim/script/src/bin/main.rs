@@ -3,101 +3,237 @@
 //!
 //! You can run this script using the following command:
 //! ```shell
-//! RUST_LOG=info cargo run --release -- --execute
+//! RUST_LOG=info cargo run --release -- execute --domain <domain> --email <path>
 //! ```
 //! or
 //! ```shell
-//! RUST_LOG=info cargo run --release -- --prove
+//! RUST_LOG=info cargo run --release -- prove --domain <domain> --email <path>
+//! ```
+//! Pass `--command` to prove a command email, such as "Send 0.1 PYUSD to recipient@example.com",
+//! instead of a receipt. It has to be sent to `--relayer`, `send@paycrypt.xyz` by default, and
+//! needs the `--account-salt` the sender's account hash is computed with.
+//!
+//! To link a user's email address to a wallet, email them the challenge printed by
+//! ```shell
+//! cargo run --release -- challenge --wallet <address>
+//! ```
+//! and prove their reply with `--registration --nonce <nonce>` and their `--account-salt`.
+//!
+//! Pass `--private` to commit only salted hashes of a receipt's receiver and sender. The values
+//! and salts are saved to `--openings`; share any of them and check them against the proof with
+//! `verify --openings <path>`.
+//!
+//! Every mode takes the `--claimant`, `--chain-id` and `--contract-address` the proof is bound to.
+//! Pass `--key-file`, `--pinned-keys` or `--key-cache` (with `--offline`) to prove without DNS.
+//!
+//! List the receipt templates and check them against the signed sample receipts with
+//! ```shell
+//! cargo run --release -- templates --check ../fixtures
+//! ```
+//!
+//! Check a saved proof with
+//! ```shell
+//! RUST_LOG=info cargo run --release -- verify --proof proof.json --vkey vkey.json
 //! ```
 
-use alloy_sol_types::SolType;
-use clap::Parser;
-use fibonacci_lib::PublicValuesStruct;
-use cfdkim::{dns, header::HEADER, public_key::retrieve_public_key, validate_header};
-use mailparse::MailHeaderMap;
-use sp1_sdk::{ProverClient, SP1Stdin};
-use std::env;
-use std::fs::File;
-use std::io::Read;
-use std::sync::Arc;
-use tokio;
-use trust_dns_resolver::TokioAsyncResolver;
-
-const ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-elf");
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    let from_domain = &args[2];
-    let email_path = &args[3];
-
-    let mut file = File::open(email_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let raw_email = contents.replace('\n', "\r\n");
-
-    let email = mailparse::parse_mail(raw_email.as_bytes())?;
-    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
-    let resolver = dns::from_tokio_resolver(resolver);
-
-    for h in email.headers.get_all_headers(HEADER) {
-        let value = String::from_utf8_lossy(h.get_value_raw());
-        let dkim_header = validate_header(&value).unwrap();
-
-        let signing_domain = dkim_header.get_required_tag("d");
-        if signing_domain.to_lowercase() != from_domain.to_lowercase() {
-            continue;
-        }
-
-        let public_key = retrieve_public_key(
-            Arc::clone(&resolver),
-            dkim_header.get_required_tag("d"),
-            dkim_header.get_required_tag("s"),
-        )
-        .await
-        .unwrap();
-
-        let mut stdin = SP1Stdin::new();
-        stdin.write::<String>(&from_domain.to_string());
-        stdin.write_vec(raw_email.as_bytes().to_vec());
-        stdin.write::<String>(&public_key.get_type());
-        stdin.write_vec(public_key.to_vec());
-
-        let client = ProverClient::new();
-
-        // if args[1]=="--prove" {
-       
-        let (pk, vk) = client.setup(ELF);
-        let mut proof = client.prove(&pk, stdin).run()?;
+use alloy_primitives::{Address, B256};
+use clap::{Parser, Subcommand, ValueEnum};
+use fibonacci_lib::builtin_templates;
+use fibonacci_lib::registration::challenge_subject;
+use fibonacci_script::openings::{check_openings, load_openings, save_openings};
+use fibonacci_script::preflight;
+use fibonacci_script::{print_committed, InputArgs, ELF};
+use sp1_sdk::{HashableKey, ProverClient, SP1ProofWithPublicValues, SP1VerifyingKey};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// The arguments for the command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
 
-        println!("Proof generated successfully.");
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the program without generating a proof.
+    Execute {
+        #[clap(flatten)]
+        input: InputArgs,
+    },
+    /// Generate a proof, verify it and save it to disk.
+    Prove {
+        #[clap(flatten)]
+        input: InputArgs,
+        #[clap(long, value_enum, default_value = "core")]
+        mode: ProofMode,
+        /// Where the proof is saved.
+        #[clap(long, default_value = "proof.json")]
+        output: PathBuf,
+        /// Where the verifying key is saved, for checking the proof later without the ELF.
+        #[clap(long, default_value = "vkey.json")]
+        vkey_output: PathBuf,
+        /// Prove even if the native pre-flight check fails.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Verify a saved proof and print the public values it commits to.
+    Verify {
+        /// The proof to verify.
+        #[clap(long, default_value = "proof.json")]
+        proof: PathBuf,
+        /// Verify against this program ELF instead of the one built into the script.
+        #[clap(long, conflicts_with = "vkey")]
+        elf: Option<PathBuf>,
+        /// Verify against a verifying key saved by `prove`.
+        #[clap(long)]
+        vkey: Option<PathBuf>,
+        /// Check openings revealed from a private receipt proof against it and print their values.
+        #[clap(long)]
+        openings: Option<PathBuf>,
+    },
+    /// List the built-in receipt templates and their hashes.
+    Templates {
+        /// Also run each template against its sample receipt in this fixtures directory.
+        #[clap(long)]
+        check: Option<PathBuf>,
+    },
+    /// Print a fresh nonce and the subject of a registration challenge to email a user. Prove
+    /// their reply with `--registration --nonce <nonce>`.
+    Challenge {
+        /// The wallet the user is asked to link their email address to.
+        #[clap(long)]
+        wallet: Address,
+    },
+}
 
-        let domain_hash= proof.public_values.read::<u8>();
-        println!("from_domain_hash: {:?}", domain_hash);
+/// The kind of proof to generate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum ProofMode {
+    Core,
+    Compressed,
+    Plonk,
+    Groth16,
+}
 
-        let public_key_hash= proof.public_values.read::<u8>();
-        println!("public_key_hash: {:?}", public_key_hash);
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
 
-        let result= proof.public_values.read::<bool>();
-        println!("result: {:?}", result);
+    // Parse the command line arguments.
+    let args = Args::parse();
 
-        let receiver= proof.public_values.read::<String>();
-        println!("receiver: {:?}", receiver);
+    // Setup the prover client.
+    let client = ProverClient::new();
 
-        let amount= proof.public_values.read::<String>();
-        println!("amount: {:?}", amount);
+    match args.command {
+        Command::Execute { input } => {
+            let (stdin, openings) = input.load(None).await?;
 
-        let sender= proof.public_values.read::<String>();
-        println!("sender: {:?}", sender);
-        
+            // Execute the program.
+            let (output, report) = client.execute(ELF, stdin).run()?;
+            println!("Program executed successfully.");
 
-        client.verify(&proof, &vk).expect("verification failed");
+            // Read the output.
+            print_committed(output.as_slice())?;
+            if let Some(openings) = openings {
+                save_openings(&input.mode.openings, &openings, output.as_slice())?;
+            }
 
-        proof.save("proof.json").expect("saving proof failed");
-        return Ok(());
- 
+            // Record the number of cycles executed.
+            println!("Number of cycles: {}", report.total_instruction_count());
+        }
+        Command::Prove {
+            input,
+            mode,
+            output,
+            vkey_output,
+            force,
+        } => {
+            let (stdin, openings) = input.load(Some(force)).await?;
+
+            // Setup the program for proving.
+            let (pk, vk) = client.setup(ELF);
+
+            // Generate the proof.
+            let prover = client.prove(&pk, stdin);
+            let proof = match mode {
+                ProofMode::Core => prover.run(),
+                ProofMode::Compressed => prover.compressed().run(),
+                ProofMode::Plonk => prover.plonk().run(),
+                ProofMode::Groth16 => prover.groth16().run(),
+            }?;
+            println!("Successfully generated proof!");
+
+            print_committed(proof.public_values.as_slice())?;
+            if let Some(openings) = openings {
+                save_openings(
+                    &input.mode.openings,
+                    &openings,
+                    proof.public_values.as_slice(),
+                )?;
+            }
+
+            // Verify the proof.
+            client.verify(&proof, &vk)?;
+            println!("Successfully verified proof!");
+
+            proof.save(&output)?;
+            println!("Proof saved to {}", output.display());
+            fs::write(&vkey_output, serde_json::to_string(&vk)?)?;
+            println!("Verifying key saved to {}", vkey_output.display());
+        }
+        Command::Verify {
+            proof,
+            elf,
+            vkey,
+            openings,
+        } => {
+            let proof = SP1ProofWithPublicValues::load(&proof)?;
+            let vk: SP1VerifyingKey = match (elf, vkey) {
+                (_, Some(vkey)) => serde_json::from_str(&fs::read_to_string(vkey)?)?,
+                (Some(elf), None) => client.setup(&fs::read(elf)?).1,
+                (None, None) => client.setup(ELF).1,
+            };
+            println!("vkey: {}", vk.bytes32());
+
+            client.verify(&proof, &vk)?;
+            println!("Successfully verified proof!");
+
+            print_committed(proof.public_values.as_slice())?;
+            if let Some(path) = openings {
+                let openings = load_openings(&path)?;
+                check_openings(&openings, proof.public_values.as_slice())?;
+                for opening in openings {
+                    println!("opened {}: {:?}", opening.field, opening.value);
+                }
+            }
+        }
+        Command::Templates { check } => {
+            for template in builtin_templates() {
+                println!(
+                    "{}\t0x{}\t{}",
+                    template.id,
+                    hex::encode(template.hash()),
+                    template.sender_domain
+                );
+            }
+            if let Some(dir) = check {
+                preflight::check_fixtures(&dir).await?;
+            }
+        }
+        Command::Challenge { wallet } => {
+            let nonce = B256::random();
+            println!("nonce: {}", nonce);
+            println!(
+                "subject: {}",
+                challenge_subject(&wallet.into_array(), &nonce.0)
+            );
+        }
     }
 
-    println!("Invalid from_domain.");
     Ok(())
 }
@@ -0,0 +1,189 @@
+//! Reading emails from `.eml` and mbox files.
+//!
+//! DKIM hashes the exact bytes of the message, so everything here works on raw bytes: 8-bit
+//! content is passed through untouched and only line endings are rewritten, and only where a
+//! bare LF has to become CRLF. The only other change is undoing the `>From ` quoting mbox writers
+//! add to body lines.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Reads the message at `index` from an `.eml` or mbox file, ready to be hashed.
+pub fn load_email(path: &Path, index: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let contents = fs::read(path)?;
+    let messages = split_messages(&contents);
+    let count = messages.len();
+    let message = messages.into_iter().nth(index).ok_or_else(|| {
+        format!(
+            "{} contains {} message(s), no message at index {}",
+            path.display(),
+            count,
+            index
+        )
+    })?;
+    Ok(normalize_line_endings(&message))
+}
+
+/// Splits an mbox file into its messages, dropping each `From ` envelope line and unquoting
+/// `>From ` lines.
+///
+/// A `From ` line only starts a new message at the top of the file or after a blank line. A plain
+/// `.eml` file comes back as a single message, untouched.
+pub fn split_messages(contents: &[u8]) -> Vec<Vec<u8>> {
+    if !contents.starts_with(b"From ") {
+        return vec![contents.to_vec()];
+    }
+
+    let mut messages = Vec::new();
+    let mut start = 0;
+    let mut line_start = 0;
+    let mut previous_blank = true;
+
+    while line_start < contents.len() {
+        let line_end = contents[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(contents.len(), |i| line_start + i + 1);
+        let line = &contents[line_start..line_end];
+
+        if previous_blank && line.starts_with(b"From ") {
+            if line_start > 0 {
+                messages.push(unquote_from_lines(trim_separator(
+                    &contents[start..line_start],
+                )));
+            }
+            start = line_end;
+        }
+        previous_blank = line == b"\n" || line == b"\r\n";
+        line_start = line_end;
+    }
+
+    messages.push(unquote_from_lines(trim_separator(&contents[start..])));
+    messages
+}
+
+/// Removes one `>` from every line that is `From ` behind one or more `>`, the mboxrd quoting
+/// that keeps body lines from being read as envelope lines.
+fn unquote_from_lines(message: &[u8]) -> Vec<u8> {
+    let mut unquoted = Vec::with_capacity(message.len());
+    for line in message.split_inclusive(|&b| b == b'\n') {
+        let quotes = line.iter().take_while(|&&b| b == b'>').count();
+        if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            unquoted.extend_from_slice(&line[1..]);
+        } else {
+            unquoted.extend_from_slice(line);
+        }
+    }
+    unquoted
+}
+
+/// Removes the blank line mbox writers put between messages.
+fn trim_separator(message: &[u8]) -> &[u8] {
+    if message.ends_with(b"\r\n\r\n") {
+        &message[..message.len() - 2]
+    } else if message.ends_with(b"\n\n") {
+        &message[..message.len() - 1]
+    } else {
+        message
+    }
+}
+
+/// Converts bare LF line endings to CRLF, leaving existing CRLF pairs alone.
+pub fn normalize_line_endings(message: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(message.len() + message.len() / 32);
+    let mut previous = None;
+    for &byte in message {
+        if byte == b'\n' && previous != Some(b'\r') {
+            normalized.push(b'\r');
+        }
+        normalized.push(byte);
+        previous = Some(byte);
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eml_is_one_untouched_message() {
+        let eml = b"Subject: hi\r\n\r\n>From the top\r\nFrom here\r\n";
+        assert_eq!(split_messages(eml), vec![eml.to_vec()]);
+    }
+
+    #[test]
+    fn splits_mbox_on_envelope_lines() {
+        let mbox = b"From a@example.com Mon Jan  1 00:00:00 2024\n\
+            Subject: one\n\nbody one\n\n\
+            From b@example.com Mon Jan  1 00:00:00 2024\n\
+            Subject: two\n\nbody two\nFrom inside a paragraph\n";
+        assert_eq!(
+            split_messages(mbox),
+            vec![
+                b"Subject: one\n\nbody one\n".to_vec(),
+                b"Subject: two\n\nbody two\nFrom inside a paragraph\n".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_crlf_mbox() {
+        let mbox = b"From a@example.com\r\nSubject: one\r\n\r\nbody\r\n\r\n\
+            From b@example.com\r\nSubject: two\r\n\r\nbody\r\n";
+        assert_eq!(
+            split_messages(mbox),
+            vec![
+                b"Subject: one\r\n\r\nbody\r\n".to_vec(),
+                b"Subject: two\r\n\r\nbody\r\n".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn unquotes_mboxrd_from_lines() {
+        let mbox = b"From a@example.com\n\
+            Subject: one\n\n>From the top\n>>From quoted twice\n> From a reply\n>Fromage\n";
+        assert_eq!(
+            split_messages(mbox),
+            vec![
+                b"Subject: one\n\nFrom the top\n>From quoted twice\n> From a reply\n>Fromage\n"
+                    .to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn normalizes_lf_to_crlf() {
+        assert_eq!(normalize_line_endings(b"a\nb\n\n"), b"a\r\nb\r\n\r\n");
+    }
+
+    #[test]
+    fn leaves_crlf_alone() {
+        let crlf = b"a\r\nb\r\n\r\nbody\r\n";
+        assert_eq!(normalize_line_endings(crlf), crlf);
+    }
+
+    #[test]
+    fn normalizes_mixed_line_endings_once() {
+        assert_eq!(
+            normalize_line_endings(b"a\r\nb\n\r\nc\n"),
+            b"a\r\nb\r\n\r\nc\r\n"
+        );
+        let once = normalize_line_endings(b"a\nb\r\n");
+        assert_eq!(normalize_line_endings(&once), once);
+    }
+
+    #[test]
+    fn passes_8bit_content_through() {
+        assert_eq!(
+            normalize_line_endings("Subject: ₹ 250\n\nPaid ₹ 1,500\n".as_bytes()),
+            "Subject: ₹ 250\r\n\r\nPaid ₹ 1,500\r\n".as_bytes()
+        );
+        assert_eq!(
+            normalize_line_endings(b"Subject: caf\xe9\n\n\xff\xfe\n"),
+            b"Subject: caf\xe9\r\n\r\n\xff\xfe\r\n"
+        );
+    }
+}
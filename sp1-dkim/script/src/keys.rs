@@ -0,0 +1,280 @@
+//! Sources for DKIM public keys.
+//!
+//! Keys are normally published as DNS TXT records, but selectors get rotated and old records
+//! disappear, so a key can also come from a local file, a pinned-keys config or an on-disk cache.
+//! [`KeySources`] tries each configured source in turn and stores whatever it finds in the cache.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use cfdkim::{dns, public_key::retrieve_public_key, DkimPublicKey};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// Command line options selecting where DKIM public keys come from.
+#[derive(clap::Args, Debug, Clone)]
+pub struct KeyArgs {
+    /// Use the DKIM TXT record or PEM public key in this file instead of looking it up.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// JSON file of pinned keys, each with a `domain`, `selector` and `record`.
+    #[clap(long)]
+    pub pinned_keys: Option<PathBuf>,
+    /// Directory in which keys are cached by domain and selector.
+    #[clap(long, env = "DKIM_KEY_CACHE")]
+    pub key_cache: Option<PathBuf>,
+    /// Never query DNS.
+    #[clap(long)]
+    pub offline: bool,
+}
+
+impl KeyArgs {
+    /// Builds the key sources in priority order: key file, pinned keys, cache, then DNS.
+    pub fn sources(&self) -> Result<KeySources, Box<dyn Error>> {
+        let mut sources = Vec::new();
+        if let Some(path) = &self.key_file {
+            sources.push(KeySource::File(path.clone()));
+        }
+        if let Some(path) = &self.pinned_keys {
+            sources.push(KeySource::Pinned(load_pinned_keys(path)?));
+        }
+        let cache = self.key_cache.clone().map(KeyCache::new);
+        if let Some(cache) = &cache {
+            sources.push(KeySource::Cache(cache.clone()));
+        }
+        if !self.offline {
+            sources.push(KeySource::Dns);
+        }
+        Ok(KeySources { sources, cache })
+    }
+}
+
+/// A single place a DKIM public key can be loaded from.
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// Query DNS for the `<selector>._domainkey.<domain>` TXT record.
+    Dns,
+    /// A DKIM TXT record or PEM public key on disk, used whatever the domain and selector.
+    File(PathBuf),
+    /// Keys pinned ahead of time for specific domains and selectors.
+    Pinned(Vec<PinnedKey>),
+    /// Keys previously stored in a [`KeyCache`].
+    Cache(KeyCache),
+}
+
+impl KeySource {
+    /// Looks up the key for `domain` and `selector`, returning `None` if this source has none.
+    pub async fn lookup(
+        &self,
+        domain: &str,
+        selector: &str,
+    ) -> Result<Option<DkimPublicKey>, Box<dyn Error>> {
+        match self {
+            KeySource::Dns => {
+                let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+                let resolver = dns::from_tokio_resolver(resolver);
+                let public_key = retrieve_public_key(
+                    Arc::clone(&resolver),
+                    domain.to_string(),
+                    selector.to_string(),
+                )
+                .await?;
+                Ok(Some(public_key))
+            }
+            KeySource::File(path) => Ok(Some(parse_public_key(&fs::read_to_string(path)?)?)),
+            KeySource::Pinned(keys) => keys
+                .iter()
+                .find(|key| key.matches(domain, selector))
+                .map(|key| parse_public_key(&key.record))
+                .transpose(),
+            KeySource::Cache(cache) => cache.load(domain, selector),
+        }
+    }
+}
+
+/// An ordered list of key sources, with an optional cache that found keys are written back to.
+#[derive(Debug, Clone)]
+pub struct KeySources {
+    sources: Vec<KeySource>,
+    cache: Option<KeyCache>,
+}
+
+impl KeySources {
+    /// Returns the key from the first source that has one.
+    pub async fn resolve(
+        &self,
+        domain: &str,
+        selector: &str,
+    ) -> Result<DkimPublicKey, Box<dyn Error>> {
+        for source in &self.sources {
+            let Some(public_key) = source.lookup(domain, selector).await? else {
+                continue;
+            };
+            // A key file is used whatever the email names, so it is not cached under that name.
+            if let Some(cache) = &self.cache {
+                if !matches!(source, KeySource::Cache(_) | KeySource::File(_)) {
+                    cache.store(domain, selector, &public_key)?;
+                }
+            }
+            return Ok(public_key);
+        }
+        Err(format!(
+            "no public key found for selector {} of {}",
+            selector, domain
+        )
+        .into())
+    }
+}
+
+/// A key pinned for one domain and selector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedKey {
+    pub domain: String,
+    pub selector: String,
+    /// The DKIM TXT record (`v=DKIM1; k=rsa; p=...`) or a PEM public key.
+    pub record: String,
+}
+
+impl PinnedKey {
+    fn matches(&self, domain: &str, selector: &str) -> bool {
+        self.domain.eq_ignore_ascii_case(domain) && self.selector.eq_ignore_ascii_case(selector)
+    }
+}
+
+/// Reads a JSON array of [`PinnedKey`]s.
+pub fn load_pinned_keys(path: &Path) -> Result<Vec<PinnedKey>, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// A directory of keys stored as `<domain>/<selector>.json`.
+#[derive(Debug, Clone)]
+pub struct KeyCache {
+    dir: PathBuf,
+}
+
+/// The on-disk form of a cached key, in the same encoding the program reads it in.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedKey {
+    key_type: String,
+    key: String,
+}
+
+impl KeyCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The entry for a domain and selector. Both come from the email, so they must be DNS names
+    /// to keep the path inside the cache directory.
+    fn path(&self, domain: &str, selector: &str) -> Result<PathBuf, Box<dyn Error>> {
+        if !is_dns_name(domain) || !is_dns_name(selector) {
+            return Err(format!(
+                "selector {:?} of {:?} is not a DNS name and cannot be cached",
+                selector, domain
+            )
+            .into());
+        }
+        Ok(self
+            .dir
+            .join(domain.to_lowercase())
+            .join(format!("{}.json", selector.to_lowercase())))
+    }
+
+    /// Loads a cached key, or `None` if there isn't one.
+    pub fn load(
+        &self,
+        domain: &str,
+        selector: &str,
+    ) -> Result<Option<DkimPublicKey>, Box<dyn Error>> {
+        let path = self.path(domain, selector)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let cached: CachedKey = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let key = hex::decode(cached.key)?;
+        Ok(Some(DkimPublicKey::from_vec_with_type(
+            &key,
+            &cached.key_type,
+        )))
+    }
+
+    /// Stores a key, replacing any previous entry for the same domain and selector.
+    pub fn store(
+        &self,
+        domain: &str,
+        selector: &str,
+        public_key: &DkimPublicKey,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.path(domain, selector)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let cached = CachedKey {
+            key_type: public_key.get_type(),
+            key: hex::encode(public_key.to_vec()),
+        };
+        fs::write(path, serde_json::to_string_pretty(&cached)?)?;
+        Ok(())
+    }
+}
+
+/// Whether `name` is one or more dot-separated DNS labels of letters, digits, `-` and `_`.
+fn is_dns_name(name: &str) -> bool {
+    name.split('.').all(|label| {
+        (1..=63).contains(&label.len())
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    })
+}
+
+/// Parses a DKIM TXT record or a PEM-encoded RSA public key.
+///
+/// TXT records may be given as they appear in a zone file, split into several quoted strings.
+pub fn parse_public_key(record: &str) -> Result<DkimPublicKey, Box<dyn Error>> {
+    let record = record.trim();
+    if record.starts_with("-----BEGIN") {
+        let key = RsaPublicKey::from_public_key_pem(record)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(record))?;
+        return Ok(DkimPublicKey::Rsa(key));
+    }
+
+    let record: String = if record.contains('"') {
+        record.split('"').skip(1).step_by(2).collect()
+    } else {
+        record.to_string()
+    };
+
+    let mut key_type = "rsa".to_string();
+    let mut key_data = None;
+    for tag in record.split(';') {
+        let Some((name, value)) = tag.split_once('=') else {
+            continue;
+        };
+        match name.trim() {
+            "k" => key_type = value.trim().to_lowercase(),
+            "p" => key_data = Some(value.split_whitespace().collect::<String>()),
+            _ => {}
+        }
+    }
+    let key_data = key_data.ok_or("DKIM record has no p= tag")?;
+    if key_data.is_empty() {
+        return Err("DKIM key has been revoked (empty p= tag)".into());
+    }
+    let key_data = STANDARD.decode(key_data)?;
+
+    match key_type.as_str() {
+        "rsa" => {
+            let key = RsaPublicKey::from_public_key_der(&key_data)
+                .or_else(|_| RsaPublicKey::from_pkcs1_der(&key_data))?;
+            Ok(DkimPublicKey::Rsa(key))
+        }
+        "ed25519" => Ok(DkimPublicKey::from_vec_with_type(&key_data, "ed25519")),
+        other => Err(format!("unsupported DKIM key type {}", other).into()),
+    }
+}
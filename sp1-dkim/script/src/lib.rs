@@ -0,0 +1,504 @@
+//! Helpers shared by the `fibonacci` and `evm` binaries: fetching the DKIM public key, laying out
+//! the program inputs and reporting the public values.
+
+use alloy_primitives::{Address, B256};
+use alloy_sol_types::SolType;
+use cfdkim::{header::HEADER, validate_header, DkimPublicKey};
+use fibonacci_lib::account::ACCOUNT_SALT_LEN;
+use fibonacci_lib::alignment::from_address;
+use fibonacci_lib::command::format_decimal;
+use fibonacci_lib::{
+    builtin_templates, committed_mode, find_template, Alignment, Amount, CommandKind,
+    CommandValuesStruct, EmailMode, ErrorCode, ExtractedField, ExtractionStatus, FieldSalts,
+    PublicValuesStruct, ReceiptTemplate, RecipientKind, Region, RegistrationValuesStruct,
+    TimeWindow, DEFAULT_RELAYER, DEFAULT_TEMPLATE,
+};
+use ingest::load_email;
+use keys::{KeyArgs, KeySources};
+use mailparse::MailHeaderMap;
+use openings::{receipt_openings, Opening};
+use sp1_sdk::SP1Stdin;
+use std::error::Error;
+use std::path::PathBuf;
+
+pub mod ingest;
+pub mod keys;
+pub mod openings;
+pub mod preflight;
+
+/// The ELF (executable and linkable format) file for the DKIM program.
+pub const ELF: &[u8] = include_bytes!("../../elf/riscv32im-succinct-zkvm-elf");
+
+/// Command line options naming the email to prove and when it must have been sent.
+#[derive(clap::Args, Debug, Clone)]
+pub struct EmailArgs {
+    /// The domain whose DKIM signature is checked.
+    #[clap(long)]
+    pub domain: String,
+    /// Path to the `.eml` or mbox file.
+    #[clap(long)]
+    pub email: PathBuf,
+    /// Which message to prove when the file is an mbox holding several.
+    #[clap(long, default_value_t = 0)]
+    pub message: usize,
+    /// Refuse emails sent before this time, in unix seconds. The proof commits to the bound.
+    #[clap(long)]
+    pub not_before: Option<u64>,
+    /// Refuse emails sent after this time, in unix seconds. The proof commits to the bound.
+    #[clap(long)]
+    pub not_after: Option<u64>,
+}
+
+impl EmailArgs {
+    /// Reads the chosen message, ready to be hashed.
+    pub fn load(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        load_email(&self.email, self.message)
+    }
+
+    pub fn window(&self) -> TimeWindow {
+        TimeWindow::new(self.not_before, self.not_after)
+    }
+}
+
+/// Command line options naming who may redeem the proof, and where.
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct ClaimArgs {
+    /// The address the payout goes to. The proof is useless to anyone else.
+    #[clap(long)]
+    pub claimant: Address,
+    /// The ID of the chain the proof is redeemed on.
+    #[clap(long)]
+    pub chain_id: u64,
+    /// The contract the proof is redeemed with.
+    #[clap(long)]
+    pub contract_address: Address,
+}
+
+/// Command line options choosing what the program reads the email as.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ModeArgs {
+    /// The ID of the receipt template to extract the payment with.
+    #[clap(long, default_value = DEFAULT_TEMPLATE)]
+    pub template: String,
+    /// Prove a command email, such as one with the subject "Send 0.1 PYUSD to
+    /// recipient@example.com", instead of a receipt.
+    #[clap(long, conflicts_with = "template")]
+    pub command: bool,
+    /// Prove a reply to the registration challenge with --nonce, linking the sender's account to
+    /// the wallet in its subject, instead of a receipt.
+    #[clap(long, conflicts_with_all = ["template", "command"])]
+    pub registration: bool,
+    /// Commit salted hashes of the receipt's receiver and sender instead of their values, and
+    /// save the openings to --openings.
+    #[clap(long, conflicts_with_all = ["command", "registration"])]
+    pub private: bool,
+    /// Where the openings of a private receipt are saved.
+    #[clap(long, default_value = "openings.json")]
+    pub openings: PathBuf,
+    /// The relayer a command or registration email has to be sent to, in its signed To or Cc
+    /// header.
+    #[clap(long, default_value = DEFAULT_RELAYER)]
+    pub relayer: String,
+    /// The 32-byte hex salt the sender's account hash is computed with, kept private. Required
+    /// with --command and --registration.
+    #[clap(long)]
+    pub account_salt: Option<B256>,
+    /// The nonce of the registration challenge, as printed by `challenge`.
+    #[clap(long)]
+    pub nonce: Option<B256>,
+}
+
+impl ModeArgs {
+    /// Looks up the template, or checks the relayer address and account salt, whichever the mode
+    /// needs.
+    pub fn inputs(&self) -> Result<ModeInputs, Box<dyn Error>> {
+        if !self.command && !self.registration {
+            let template = load_template(&self.template)?;
+            if !self.private {
+                return Ok(ModeInputs::Receipt(template));
+            }
+            // Fresh salts for every proof, so two proofs of the same account do not share hashes.
+            let salts = FieldSalts {
+                receiver: B256::random().0,
+                sender: B256::random().0,
+            };
+            return Ok(ModeInputs::PrivateReceipt { template, salts });
+        }
+        let relayer = from_address(&self.relayer)
+            .ok_or_else(|| format!("--relayer {} is not an email address", self.relayer))?;
+        let account_salt = self
+            .account_salt
+            .ok_or("--command and --registration need the --account-salt of the sender's account")?
+            .0;
+        if !self.registration {
+            return Ok(ModeInputs::Command {
+                relayer,
+                account_salt,
+            });
+        }
+        let nonce = self
+            .nonce
+            .ok_or("--registration needs the --nonce of the challenge")?;
+        Ok(ModeInputs::Registration {
+            relayer,
+            account_salt,
+            nonce: nonce.0,
+        })
+    }
+}
+
+/// The inputs only one mode of the program reads.
+#[derive(Debug, Clone)]
+pub enum ModeInputs {
+    /// Read the email as a receipt with this template.
+    Receipt(ReceiptTemplate),
+    /// Read the email as a receipt, committing the receiver and sender hashed with these salts.
+    PrivateReceipt {
+        template: ReceiptTemplate,
+        salts: FieldSalts,
+    },
+    /// Read the email as a command sent to this lowercased relayer address, hashing the sender
+    /// into an account with the salt.
+    Command {
+        relayer: String,
+        account_salt: [u8; ACCOUNT_SALT_LEN],
+    },
+    /// Read the email as a reply to the registration challenge with this nonce, sent to the
+    /// relayer.
+    Registration {
+        relayer: String,
+        account_salt: [u8; ACCOUNT_SALT_LEN],
+        nonce: [u8; 32],
+    },
+}
+
+impl ModeInputs {
+    pub fn mode(&self) -> EmailMode {
+        match self {
+            ModeInputs::Receipt(_) => EmailMode::Receipt,
+            ModeInputs::PrivateReceipt { .. } => EmailMode::PrivateReceipt,
+            ModeInputs::Command { .. } => EmailMode::Command,
+            ModeInputs::Registration { .. } => EmailMode::Registration,
+        }
+    }
+}
+
+/// Looks up the public key for the first DKIM signature whose `d=` tag matches `from_domain`.
+pub async fn fetch_public_key(
+    from_domain: &str,
+    raw_email: &[u8],
+    key_sources: &KeySources,
+) -> Result<DkimPublicKey, Box<dyn Error>> {
+    let email = mailparse::parse_mail(raw_email)?;
+    let mut signing_domains = Vec::new();
+
+    for h in email.headers.get_all_headers(HEADER) {
+        let value = String::from_utf8_lossy(h.get_value_raw());
+        let dkim_header = validate_header(&value)?;
+
+        let signing_domain = dkim_header.get_required_tag("d");
+        if signing_domain.to_lowercase() != from_domain.to_lowercase() {
+            signing_domains.push(signing_domain);
+            continue;
+        }
+
+        let public_key = key_sources
+            .resolve(&signing_domain, &dkim_header.get_required_tag("s"))
+            .await?;
+        return Ok(public_key);
+    }
+
+    if signing_domains.is_empty() {
+        Err("the email has no DKIM-Signature header".into())
+    } else {
+        Err(format!(
+            "no DKIM signature with d={} (the email is signed by {})",
+            from_domain,
+            signing_domains.join(", ")
+        )
+        .into())
+    }
+}
+
+/// The email to prove and everything the program reads alongside it.
+#[derive(clap::Args, Debug)]
+pub struct InputArgs {
+    #[clap(flatten)]
+    pub email: EmailArgs,
+    #[clap(flatten)]
+    pub mode: ModeArgs,
+    #[clap(flatten)]
+    pub keys: KeyArgs,
+    #[clap(flatten)]
+    pub claim: ClaimArgs,
+}
+
+impl InputArgs {
+    /// Loads the email and its DKIM public key and lays them out as program inputs, along with
+    /// the openings to save for a private receipt.
+    ///
+    /// With `preflight` set, the email is first checked natively; the flag says whether to carry
+    /// on if that check fails. A private receipt whose openings cannot be made fails to load
+    /// either way, as its proof could never be opened.
+    pub async fn load(
+        &self,
+        preflight: Option<bool>,
+    ) -> Result<(SP1Stdin, Option<Vec<Opening>>), Box<dyn Error>> {
+        let inputs = self.mode.inputs()?;
+        let raw_email = self.email.load()?;
+        let key_sources = self.keys.sources()?;
+        let public_key = fetch_public_key(&self.email.domain, &raw_email, &key_sources).await?;
+        let window = self.email.window();
+        if let Some(force) = preflight {
+            preflight::check(
+                &self.email.domain,
+                &raw_email,
+                &public_key,
+                &inputs,
+                window,
+                force,
+            )?;
+        }
+        let openings = match &inputs {
+            ModeInputs::PrivateReceipt { template, salts } => Some(
+                receipt_openings(
+                    &self.email.domain,
+                    &raw_email,
+                    &public_key,
+                    template,
+                    salts,
+                    window,
+                )
+                .map_err(|err| format!("the receipt openings could not be made: {}", err))?,
+            ),
+            _ => None,
+        };
+        let stdin = build_stdin(
+            &self.email.domain,
+            &raw_email,
+            &public_key,
+            &inputs,
+            window,
+            &self.claim,
+        );
+        Ok((stdin, openings))
+    }
+}
+
+/// Writes the program inputs in the order the guest reads them.
+pub fn build_stdin(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    inputs: &ModeInputs,
+    window: TimeWindow,
+    claim: &ClaimArgs,
+) -> SP1Stdin {
+    let mut stdin = SP1Stdin::new();
+    stdin.write::<u8>(&(inputs.mode() as u8));
+    stdin.write::<String>(&from_domain.to_string());
+    stdin.write_vec(raw_email.to_vec());
+    stdin.write::<String>(&public_key.get_type());
+    stdin.write_vec(public_key.to_vec());
+    match inputs {
+        ModeInputs::Receipt(template) => stdin.write_vec(ReceiptTemplate::abi_encode(template)),
+        ModeInputs::PrivateReceipt { template, salts } => {
+            stdin.write_vec(ReceiptTemplate::abi_encode(template));
+            stdin.write::<[u8; 32]>(&salts.receiver);
+            stdin.write::<[u8; 32]>(&salts.sender);
+        }
+        ModeInputs::Command {
+            relayer,
+            account_salt,
+        } => {
+            stdin.write::<String>(relayer);
+            stdin.write::<[u8; ACCOUNT_SALT_LEN]>(account_salt);
+        }
+        ModeInputs::Registration {
+            relayer,
+            account_salt,
+            nonce,
+        } => {
+            stdin.write::<String>(relayer);
+            stdin.write::<[u8; ACCOUNT_SALT_LEN]>(account_salt);
+            stdin.write::<[u8; 32]>(nonce);
+        }
+    }
+    stdin.write::<u64>(&window.not_before);
+    stdin.write::<u64>(&window.not_after);
+    stdin.write::<[u8; 20]>(&claim.claimant.into_array());
+    stdin.write::<u64>(&claim.chain_id);
+    stdin.write::<[u8; 20]>(&claim.contract_address.into_array());
+    stdin
+}
+
+/// Decodes the public values committed by the program, in whichever mode it ran, and prints them.
+pub fn print_committed(public_values: &[u8]) -> Result<(), Box<dyn Error>> {
+    match committed_mode(public_values) {
+        Some(EmailMode::Receipt | EmailMode::PrivateReceipt) => {
+            print_public_values(&PublicValuesStruct::abi_decode(public_values, true)?)
+        }
+        Some(EmailMode::Command) => {
+            print_command_values(&CommandValuesStruct::abi_decode(public_values, true)?)
+        }
+        Some(EmailMode::Registration) => {
+            print_registration_values(&RegistrationValuesStruct::abi_decode(public_values, true)?)
+        }
+        // The program commits an unknown mode in the receipt layout, with only the error set.
+        None => {
+            let public_values = PublicValuesStruct::abi_decode(public_values, true)
+                .map_err(|_| "the public values have an unknown mode")?;
+            println!("mode: unknown ({})", public_values.mode);
+            print_error_code(public_values.error_code);
+        }
+    }
+    Ok(())
+}
+
+/// Prints the decoded public values committed by the program for a receipt.
+pub fn print_public_values(public_values: &PublicValuesStruct) {
+    println!("from_domain_hash: {}", public_values.from_domain_hash);
+    println!("public_key_hash: {}", public_values.public_key_hash);
+    let template = builtin_templates()
+        .into_iter()
+        .find(|t| t.hash() == public_values.template_hash.0);
+    match template {
+        Some(template) => println!(
+            "template_hash: {} ({})",
+            public_values.template_hash, template.id
+        ),
+        None => println!(
+            "template_hash: {} (not built in)",
+            public_values.template_hash
+        ),
+    }
+    println!("nullifier: {}", public_values.nullifier);
+    println!("result: {}", public_values.result);
+    print_error_code(public_values.error_code);
+    print_alignment(public_values.from_alignment);
+    match ExtractionStatus::try_from(public_values.extraction_status) {
+        Ok(status) => println!("extraction_status: {:?}", status),
+        Err(code) => println!("extraction_status: unknown ({})", code),
+    }
+    print_field("receiver", &public_values.receiver);
+    print_field("amount_text", &public_values.amount_text);
+    print_field("sender", &public_values.sender);
+    print_field("reference", &public_values.reference);
+    match u128::try_from(public_values.amount) {
+        Ok(minor_units) => {
+            let amount = Amount {
+                minor_units,
+                currency: public_values.currency.0,
+            };
+            println!("amount: {}", amount);
+        }
+        Err(_) => println!("amount: {} (minor units)", public_values.amount),
+    }
+    print_timestamp("date", public_values.date);
+    print_timestamp("dkim_timestamp", public_values.dkim_timestamp);
+    print_timestamp("dkim_expiration", public_values.dkim_expiration);
+    print_timestamp("not_before", public_values.not_before);
+    print_timestamp("not_after", public_values.not_after);
+    println!("claimant: {}", public_values.claimant);
+    println!("chain_id: {}", public_values.chain_id);
+    println!("contract_address: {}", public_values.contract_address);
+}
+
+fn print_timestamp(name: &str, timestamp: u64) {
+    if timestamp == 0 {
+        println!("{}: none", name);
+    } else {
+        println!("{}: {}", name, timestamp);
+    }
+}
+
+/// Prints the decoded public values committed by the program for a command email.
+pub fn print_command_values(command_values: &CommandValuesStruct) {
+    println!("from_domain_hash: {}", command_values.from_domain_hash);
+    println!("public_key_hash: {}", command_values.public_key_hash);
+    println!("nullifier: {}", command_values.nullifier);
+    println!("result: {}", command_values.result);
+    print_error_code(command_values.error_code);
+    print_alignment(command_values.from_alignment);
+    println!("account_hash: {}", command_values.account_hash);
+    println!("relayer_hash: {}", command_values.relayer_hash);
+    match CommandKind::try_from(command_values.command) {
+        Ok(command) => println!("command: {:?}", command),
+        Err(code) => println!("command: unknown ({})", code),
+    }
+    println!(
+        "amount: {} {}",
+        format_decimal(command_values.amount, command_values.decimals),
+        command_values.token
+    );
+    match RecipientKind::try_from(command_values.recipient_kind) {
+        Ok(kind) => println!("recipient: {:?} ({:?})", command_values.recipient, kind),
+        Err(code) => println!(
+            "recipient: {:?} (unknown kind {})",
+            command_values.recipient, code
+        ),
+    }
+    println!("recipient_address: {}", command_values.recipient_address);
+    print_timestamp("date", command_values.date);
+    print_timestamp("dkim_timestamp", command_values.dkim_timestamp);
+    print_timestamp("dkim_expiration", command_values.dkim_expiration);
+    print_timestamp("not_before", command_values.not_before);
+    print_timestamp("not_after", command_values.not_after);
+    println!("claimant: {}", command_values.claimant);
+    println!("chain_id: {}", command_values.chain_id);
+    println!("contract_address: {}", command_values.contract_address);
+}
+
+/// Prints the decoded public values committed by the program for a registration reply.
+pub fn print_registration_values(registration_values: &RegistrationValuesStruct) {
+    println!("from_domain_hash: {}", registration_values.from_domain_hash);
+    println!("public_key_hash: {}", registration_values.public_key_hash);
+    println!("nullifier: {}", registration_values.nullifier);
+    println!("result: {}", registration_values.result);
+    print_error_code(registration_values.error_code);
+    print_alignment(registration_values.from_alignment);
+    println!("account_hash: {}", registration_values.account_hash);
+    println!("relayer_hash: {}", registration_values.relayer_hash);
+    println!("wallet: {}", registration_values.wallet);
+    println!("nonce: {}", registration_values.nonce);
+    print_timestamp("date", registration_values.date);
+    print_timestamp("dkim_timestamp", registration_values.dkim_timestamp);
+    print_timestamp("dkim_expiration", registration_values.dkim_expiration);
+    print_timestamp("not_before", registration_values.not_before);
+    print_timestamp("not_after", registration_values.not_after);
+    println!("claimant: {}", registration_values.claimant);
+    println!("chain_id: {}", registration_values.chain_id);
+    println!("contract_address: {}", registration_values.contract_address);
+}
+
+fn print_error_code(error_code: u8) {
+    match ErrorCode::try_from(error_code) {
+        Ok(code) => println!("error_code: {:?} ({})", code, code.explanation()),
+        Err(code) => println!("error_code: unknown ({})", code),
+    }
+}
+
+fn print_alignment(from_alignment: u8) {
+    match Alignment::try_from(from_alignment) {
+        Ok(alignment) => println!("from_alignment: {:?}", alignment),
+        Err(code) => println!("from_alignment: unknown ({})", code),
+    }
+}
+
+fn print_field(name: &str, field: &ExtractedField) {
+    match Region::try_from(field.region) {
+        Ok(region) => println!("{}: {:?} ({:?})", name, field.value, region),
+        Err(code) => println!("{}: {:?} (unknown region {})", name, field.value, code),
+    }
+    if !field.hash.is_zero() {
+        println!("{}_hash: {}", name, field.hash);
+    }
+}
+
+/// Looks up a built-in template, listing the available IDs if there is none by that name.
+pub fn load_template(id: &str) -> Result<ReceiptTemplate, Box<dyn Error>> {
+    find_template(id).ok_or_else(|| {
+        let ids: Vec<String> = builtin_templates().into_iter().map(|t| t.id).collect();
+        format!("unknown template {} (available: {})", id, ids.join(", ")).into()
+    })
+}
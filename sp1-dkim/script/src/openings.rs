@@ -0,0 +1,103 @@
+//! Openings of the receipt fields a private receipt proof commits only as salted hashes.
+//!
+//! The program commits `sha256(value || salt)` for the receiver and sender. The host extracts the
+//! same values natively and saves each with its salt, so the user can later hand the opening of
+//! one field to a counterparty, who checks it against the proof's public values.
+
+use crate::preflight::verify_email;
+use alloy_primitives::B256;
+use alloy_sol_types::SolType;
+use cfdkim::DkimPublicKey;
+use fibonacci_lib::{
+    extract_payment, field_commitment, FieldSalts, PublicValuesStruct, ReceiptTemplate, TimeWindow,
+};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A committed field's value and the salt it was hashed with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Opening {
+    /// `receiver` or `sender`.
+    pub field: String,
+    pub value: String,
+    /// The salt, as `0x` hex.
+    pub salt: String,
+    /// The hash committed in the field's `hash`, as `0x` hex.
+    pub commitment: String,
+}
+
+impl Opening {
+    fn new(field: &str, value: &str, salt: &[u8; 32]) -> Self {
+        Self {
+            field: field.to_string(),
+            value: value.to_string(),
+            salt: B256::from(*salt).to_string(),
+            commitment: B256::from(field_commitment(value, salt)).to_string(),
+        }
+    }
+}
+
+/// Verifies the email natively and extracts the receiver and sender from the signature that
+/// verified, the way the program does, pairing each with its salt.
+pub fn receipt_openings(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    template: &ReceiptTemplate,
+    salts: &FieldSalts,
+    window: TimeWindow,
+) -> Result<Vec<Opening>, Box<dyn Error>> {
+    let signed = verify_email(from_domain, raw_email, public_key, window)?;
+    let payment = extract_payment(&signed, template).map_err(|status| {
+        format!(
+            "the receiver and sender could not be extracted ({:?})",
+            status
+        )
+    })?;
+    Ok(vec![
+        Opening::new("receiver", &payment.receiver.value, &salts.receiver),
+        Opening::new("sender", &payment.sender.value, &salts.sender),
+    ])
+}
+
+/// Checks that each opening hashes to the commitment the public values hold for its field.
+pub fn check_openings(openings: &[Opening], public_values: &[u8]) -> Result<(), Box<dyn Error>> {
+    let public_values = PublicValuesStruct::abi_decode(public_values, true)?;
+    for opening in openings {
+        let committed = match opening.field.as_str() {
+            "receiver" => public_values.receiver.hash,
+            "sender" => public_values.sender.hash,
+            other => return Err(format!("no field {} is committed as a hash", other).into()),
+        };
+        let salt = B256::from_str(&opening.salt)?;
+        if field_commitment(&opening.value, &salt.0) != committed.0 {
+            return Err(format!(
+                "the {} opening does not match the hash the proof commits to",
+                opening.field
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks the openings against the public values and writes them to `path`.
+pub fn save_openings(
+    path: &Path,
+    openings: &[Opening],
+    public_values: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    check_openings(openings, public_values)?;
+    fs::write(path, serde_json::to_string_pretty(openings)?)?;
+    println!("Openings written to {}", path.display());
+    Ok(())
+}
+
+/// Reads openings saved by [`save_openings`], or any subset of them.
+pub fn load_openings(path: &Path) -> Result<Vec<Opening>, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
@@ -0,0 +1,326 @@
+//! Native checks run before proving.
+//!
+//! Proving takes minutes, so the host first verifies the DKIM signature with `cfdkim` and runs the
+//! same payment extraction as the program. Anything that would make the proof useless is reported
+//! up front.
+
+use crate::keys::KeyArgs;
+use crate::{fetch_public_key, ModeInputs};
+use alloy_primitives::{Address, B256};
+use cfdkim::{verify_email_with_public_key, DKIMError, DkimPublicKey};
+use fibonacci_lib::alignment::{from_address, from_alignment, Alignment};
+use fibonacci_lib::command::{addressed_to, signed_command};
+use fibonacci_lib::dkim::{
+    signatures, split_message, verify_signed_content, SignedContent, VerifyError,
+};
+use fibonacci_lib::registration::signed_registration;
+use fibonacci_lib::{
+    builtin_templates, extract_payment, Command, ErrorCode, ExtractionStatus, Payment,
+    ReceiptTemplate, Registration, TimeWindow,
+};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Why an email would not produce a useful proof.
+#[derive(Debug)]
+pub enum PreflightError {
+    /// The email could not be parsed.
+    Parse(mailparse::MailParseError),
+    /// DKIM verification failed.
+    Dkim(DKIMError),
+    /// DKIM verification did not pass, without a more specific error.
+    NotVerified(String),
+    /// The program would reject the email with this code.
+    Rejected(ErrorCode),
+    /// The signature's `x=` is not after its `t=`, which the program refuses.
+    ExpiredAtSigning,
+    /// The email was not sent within the requested window, which the program refuses. Holds the
+    /// send time, if the email has one.
+    OutsideWindow(Option<u64>),
+    /// The signature is valid but the template could not be applied to the email.
+    Extraction(ExtractionStatus),
+}
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightError::Parse(err) => write!(f, "the email could not be parsed: {}", err),
+            PreflightError::Dkim(DKIMError::BodyHashDidNotVerify) => write!(
+                f,
+                "body hash mismatch: the body was changed after signing (check that line endings \
+                 were not altered)"
+            ),
+            PreflightError::Dkim(DKIMError::SignatureDidNotVerify) => write!(
+                f,
+                "signature invalid: the signed headers were changed or the public key does not \
+                 belong to this selector"
+            ),
+            PreflightError::Dkim(DKIMError::SignatureExpired) => {
+                write!(f, "signature expired: the x= expiry tag has passed")
+            }
+            PreflightError::Dkim(err) => write!(f, "DKIM verification failed: {}", err),
+            PreflightError::NotVerified(summary) => {
+                write!(f, "DKIM verification did not pass: {}", summary)
+            }
+            PreflightError::Rejected(code) => write!(f, "{}", code.explanation()),
+            PreflightError::ExpiredAtSigning => write!(
+                f,
+                "the signature's x= expiry is not after its t= timestamp, so the program would \
+                 refuse it"
+            ),
+            PreflightError::OutsideWindow(Some(sent_at)) => write!(
+                f,
+                "the email was sent at {} (unix seconds), outside --not-before/--not-after",
+                sent_at
+            ),
+            PreflightError::OutsideWindow(None) => write!(
+                f,
+                "the email has no signed t= tag or Date header to check --not-before/--not-after \
+                 against"
+            ),
+            PreflightError::Extraction(ExtractionStatus::DomainMismatch) => write!(
+                f,
+                "the signature is valid but the email is not from the template's sender domain"
+            ),
+            PreflightError::Extraction(ExtractionStatus::InvalidTemplate) => {
+                write!(f, "the template is invalid")
+            }
+            PreflightError::Extraction(_) => write!(
+                f,
+                "the signature is valid but the template's fields were not found in the signed \
+                 content"
+            ),
+        }
+    }
+}
+
+impl Error for PreflightError {}
+
+/// Runs the checks the program makes on every email and returns what its signature covers.
+pub fn verify_email(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    window: TimeWindow,
+) -> Result<SignedContent, PreflightError> {
+    mailparse::parse_mail(raw_email).map_err(PreflightError::Parse)?;
+    // The program's own checks, with the native cfdkim call in place of its own.
+    let (signed, _) = verify_signed_content(raw_email, from_domain, window, |signed, isolated| {
+        verify_signature(from_domain, signed, isolated, public_key)
+    })
+    .map_err(|err| match err {
+        VerifyError::Unverified(err) => {
+            err.unwrap_or(PreflightError::Rejected(ErrorCode::ParseError))
+        }
+        VerifyError::Headers(code) => PreflightError::Rejected(code),
+        VerifyError::ExpiredAtSigning(_) => PreflightError::ExpiredAtSigning,
+        VerifyError::OutsideWindow(timestamps) => {
+            PreflightError::OutsideWindow(timestamps.sent_at())
+        }
+    })?;
+    Ok(signed)
+}
+
+/// Verifies one signature, given the email with every other signature from its domain removed.
+fn verify_signature(
+    from_domain: &str,
+    signed: &SignedContent,
+    isolated: &[u8],
+    public_key: &DkimPublicKey,
+) -> Result<(), PreflightError> {
+    if !signed
+        .signature
+        .key_type()
+        .eq_ignore_ascii_case(&public_key.get_type())
+    {
+        return Err(PreflightError::Rejected(ErrorCode::KeyTypeMismatch));
+    }
+    let email = mailparse::parse_mail(isolated).map_err(PreflightError::Parse)?;
+    let result = verify_email_with_public_key(from_domain, &email, public_key)
+        .map_err(PreflightError::Dkim)?;
+    if result.summary() != "pass" {
+        return Err(match result.error() {
+            Some(err) => PreflightError::Dkim(err),
+            None => PreflightError::NotVerified(result.summary().to_string()),
+        });
+    }
+    Ok(())
+}
+
+/// Verifies the email natively and extracts the payment the program would commit.
+pub fn preflight(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    template: &ReceiptTemplate,
+    window: TimeWindow,
+) -> Result<Payment, PreflightError> {
+    let signed = verify_email(from_domain, raw_email, public_key, window)?;
+    extract_payment(&signed, template).map_err(PreflightError::Extraction)
+}
+
+/// Verifies a command email natively and parses the command the program would commit.
+pub fn preflight_command(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    relayer: &str,
+    window: TimeWindow,
+) -> Result<Command, PreflightError> {
+    let signed = verify_email(from_domain, raw_email, public_key, window)?;
+    check_sender(&signed, relayer, ErrorCode::CommandMiss)?;
+    signed_command(&signed).ok_or(PreflightError::Rejected(ErrorCode::CommandMiss))
+}
+
+/// Verifies a registration reply natively and parses the wallet the program would commit.
+pub fn preflight_registration(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    relayer: &str,
+    nonce: &[u8; 32],
+    window: TimeWindow,
+) -> Result<Registration, PreflightError> {
+    let signed = verify_email(from_domain, raw_email, public_key, window)?;
+    check_sender(&signed, relayer, ErrorCode::RegistrationMiss)?;
+    let registration = signed_registration(&signed)
+        .ok_or(PreflightError::Rejected(ErrorCode::RegistrationMiss))?;
+    if registration.nonce != *nonce {
+        return Err(PreflightError::Rejected(ErrorCode::NonceMismatch));
+    }
+    Ok(registration)
+}
+
+/// Checks the email has a single signed From address in the signing domain itself, and was sent to
+/// the relayer.
+fn check_sender(
+    signed: &SignedContent,
+    relayer: &str,
+    miss: ErrorCode,
+) -> Result<(), PreflightError> {
+    if from_alignment(signed) != Alignment::Strict {
+        return Err(PreflightError::Rejected(ErrorCode::UnalignedFrom));
+    }
+    signed
+        .header("From")
+        .and_then(from_address)
+        .ok_or(PreflightError::Rejected(miss))?;
+    if !addressed_to(signed, relayer) {
+        return Err(PreflightError::Rejected(ErrorCode::NotAddressedToRelayer));
+    }
+    Ok(())
+}
+
+/// Runs [`preflight`], [`preflight_command`] or [`preflight_registration`], whichever the mode
+/// needs, and reports the outcome, failing unless `force` is set.
+pub fn check(
+    from_domain: &str,
+    raw_email: &[u8],
+    public_key: &DkimPublicKey,
+    inputs: &ModeInputs,
+    window: TimeWindow,
+    force: bool,
+) -> Result<(), Box<dyn Error>> {
+    let outcome = match inputs {
+        ModeInputs::Receipt(template) | ModeInputs::PrivateReceipt { template, .. } => {
+            preflight(from_domain, raw_email, public_key, template, window).map(|payment| {
+                format!(
+                    "{} paid to {} from {} (reference {})",
+                    payment.amount,
+                    payment.receiver.value,
+                    payment.sender.value,
+                    payment.reference.value
+                )
+            })
+        }
+        ModeInputs::Command { relayer, .. } => {
+            preflight_command(from_domain, raw_email, public_key, relayer, window)
+                .map(|command| command.to_string())
+        }
+        ModeInputs::Registration { relayer, nonce, .. } => {
+            preflight_registration(from_domain, raw_email, public_key, relayer, nonce, window).map(
+                |registration| {
+                    format!(
+                        "link to wallet {} (nonce {})",
+                        Address::from(registration.wallet),
+                        B256::from(registration.nonce)
+                    )
+                },
+            )
+        }
+    };
+    match outcome {
+        Ok(summary) => {
+            println!("Pre-flight check passed: {}", summary);
+            Ok(())
+        }
+        Err(err) if force => {
+            println!("Pre-flight check failed, proving anyway: {}", err);
+            Ok(())
+        }
+        Err(err) => Err(format!(
+            "pre-flight check failed: {} (pass --force to prove anyway)",
+            err
+        )
+        .into()),
+    }
+}
+
+/// Runs [`preflight`] for every built-in template that has a sample receipt in `<dir>/emails`,
+/// named after the template ID, using only the keys pinned in `<dir>/keys.json`.
+pub async fn check_fixtures(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let key_args = KeyArgs {
+        key_file: None,
+        pinned_keys: Some(dir.join("keys.json")),
+        key_cache: None,
+        offline: true,
+    };
+    let key_sources = key_args.sources()?;
+
+    let mut failures = 0;
+    for template in builtin_templates() {
+        let path = dir.join("emails").join(format!("{}.eml", template.id));
+        if !path.exists() {
+            println!("{}: no fixture", template.id);
+            continue;
+        }
+        let raw_email = fs::read(&path)?;
+        let (headers, _) = split_message(&raw_email);
+        let Some(signature) = signatures(&headers).into_iter().next() else {
+            println!(
+                "{}: FAIL the fixture has no DKIM-Signature header",
+                template.id
+            );
+            failures += 1;
+            continue;
+        };
+        let public_key = fetch_public_key(&signature.domain, &raw_email, &key_sources).await?;
+        match preflight(
+            &signature.domain,
+            &raw_email,
+            &public_key,
+            &template,
+            TimeWindow::default(),
+        ) {
+            Ok(payment) => println!(
+                "{}: ok {} paid to {} from {} (reference {})",
+                template.id,
+                payment.amount,
+                payment.receiver.value,
+                payment.sender.value,
+                payment.reference.value
+            ),
+            Err(err) => {
+                println!("{}: FAIL {}", template.id, err);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{} template fixture(s) failed", failures).into());
+    }
+    Ok(())
+}